@@ -0,0 +1,532 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::slice::Iter;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use log::{info, warn};
+use prost::Message;
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use ei::ei::{Contract, Egg};
+
+pub const CARPETSAGE_CONTRACTS_URL: &str =
+    "https://raw.githubusercontent.com/carpetsage/egg/main/periodicals/data/contracts.json";
+
+/// A cached archive younger than this (1 hour) is used without asking carpetsage if it changed.
+const CACHE_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+static SHARED_ARCHIVE: Mutex<Option<(Instant, Arc<ContractArchive>)>> = Mutex::const_new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CarpetBackedUpContract {
+    pub id: String,
+    pub proto: String,
+}
+
+/// Every contract in carpetsage's `contracts.json`, decoded once and indexed.
+///
+/// # Details
+///
+/// The file lists contracts oldest first, and a contract that has been rerun shows up once per
+/// run. Queries keep that order, so the last entry of [`ContractArchive::runs`] is the newest run.
+#[derive(Debug, Clone, Default)]
+pub struct ContractArchive {
+    contracts: Vec<Contract>,
+    by_id: HashMap<String, Vec<usize>>,
+    by_egg: HashMap<Egg, Vec<usize>>,
+    by_season: HashMap<String, Vec<usize>>,
+    etag: Option<String>,
+}
+
+impl ContractArchive {
+    pub fn new(contracts: Vec<Contract>) -> Self {
+        let mut archive = Self {
+            contracts,
+            ..Default::default()
+        };
+        for (i, c) in archive.contracts.iter().enumerate() {
+            archive
+                .by_id
+                .entry(c.identifier().to_string())
+                .or_default()
+                .push(i);
+            archive.by_egg.entry(c.egg()).or_default().push(i);
+            if !c.season_id().is_empty() {
+                archive
+                    .by_season
+                    .entry(c.season_id().to_string())
+                    .or_default()
+                    .push(i);
+            }
+        }
+        archive
+    }
+
+    /// The archive shared by the whole process, loaded from the on-disk cache (or carpetsage, if
+    /// the cache is missing or old) on first use.
+    ///
+    /// # Details
+    ///
+    /// Once the shared copy is older than [`CACHE_MAX_AGE`], the next call loads it again, so a
+    /// long-running process still picks up new contracts. If that fails, the old copy is kept.
+    pub async fn shared() -> Result<Arc<ContractArchive>> {
+        let mut shared = SHARED_ARCHIVE.lock().await;
+        if let Some((loaded_at, archive)) = shared.as_ref() {
+            if loaded_at.elapsed() < CACHE_MAX_AGE {
+                return Ok(archive.clone());
+            }
+        }
+
+        match Self::load_cached(CARPETSAGE_CONTRACTS_URL, default_cache_path()).await {
+            Ok(archive) => {
+                let archive = Arc::new(archive);
+                *shared = Some((Instant::now(), archive.clone()));
+                Ok(archive)
+            }
+            Err(e) => match shared.as_mut() {
+                Some((loaded_at, archive)) => {
+                    warn!(
+                        "Cannot reload contract archive, keeping the old one: {:#}",
+                        e
+                    );
+                    // Don't retry on every call; wait for the cache to age again.
+                    *loaded_at = Instant::now();
+                    Ok(archive.clone())
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let backed_up: Vec<CarpetBackedUpContract> =
+            serde_json::from_str(json).context("Cannot parse contract archive")?;
+
+        let contracts = backed_up
+            .iter()
+            .filter_map(|c| match decode_contract(&c.proto) {
+                Ok(contract) => Some(contract),
+                Err(e) => {
+                    warn!("Skipping archived contract \"{}\": {:#}", c.id, e);
+                    None
+                }
+            })
+            .collect();
+        Ok(Self::new(contracts))
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).context(format!(
+            "Cannot read contract archive \"{}\"",
+            path.display()
+        ))?;
+        Self::from_json(&json)
+    }
+
+    pub async fn from_url(url: &str) -> Result<Self> {
+        Download::fetch(url, None)
+            .await?
+            .ok_or_else(|| anyhow!("Contract archive at \"{}\" is not modified", url))?
+            .archive()
+    }
+
+    /// Loads the archive from `cache_path`, only downloading `url` again if it changed since.
+    ///
+    /// # Details
+    ///
+    /// The ETag of the cached copy is kept next to it (`<cache_path>.etag`) and only trusted
+    /// along with a non-empty cached copy. A copy younger than [`CACHE_MAX_AGE`] is used without
+    /// a request. If the download fails, the cached copy is used as-is.
+    pub async fn load_cached(url: &str, cache_path: impl AsRef<Path>) -> Result<Self> {
+        let cache_path = cache_path.as_ref();
+        match CachedArchive::read(cache_path, CACHE_MAX_AGE) {
+            CachedArchive::Fresh(cached) => Ok(cached),
+            CachedArchive::Stale(mut cached) => {
+                if let Err(e) = cached.refresh(url, cache_path).await {
+                    warn!(
+                        "Cannot refresh contract archive, using cached copy: {:#}",
+                        e
+                    );
+                }
+                Ok(cached)
+            }
+            CachedArchive::Missing => {
+                let download = Download::fetch(url, None)
+                    .await?
+                    .ok_or_else(|| anyhow!("Contract archive at \"{}\" is not modified", url))?;
+                let fresh = download.archive()?;
+                if let Err(e) = download.save(cache_path) {
+                    warn!("Cannot cache contract archive: {:#}", e);
+                }
+                Ok(fresh)
+            }
+        }
+    }
+
+    /// Re-downloads `url` if its ETag changed, and writes the new copy to `cache_path`.
+    ///
+    /// Returns whether anything changed.
+    pub async fn refresh(&mut self, url: &str, cache_path: impl AsRef<Path>) -> Result<bool> {
+        let Some(download) = Download::fetch(url, self.etag.as_deref()).await? else {
+            return Ok(false);
+        };
+        *self = download.archive()?;
+        download.save(cache_path.as_ref())?;
+        info!("Refreshed contract archive ({} contracts)", self.len());
+        Ok(true)
+    }
+
+    /// The newest run of a contract.
+    pub fn get(&self, contract_id: &str) -> Option<&Contract> {
+        self.by_id
+            .get(contract_id)
+            .and_then(|runs| runs.last())
+            .map(|&i| &self.contracts[i])
+    }
+
+    /// Every run of a contract, oldest first.
+    pub fn runs(&self, contract_id: &str) -> Vec<&Contract> {
+        self.lookup(self.by_id.get(contract_id))
+    }
+
+    pub fn with_egg(&self, egg: Egg) -> Vec<&Contract> {
+        self.lookup(self.by_egg.get(&egg))
+    }
+
+    pub fn in_season(&self, season_id: &str) -> Vec<&Contract> {
+        self.lookup(self.by_season.get(season_id))
+    }
+
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    pub fn contracts(&self) -> Iter<'_, Contract> {
+        self.contracts.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.contracts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contracts.is_empty()
+    }
+
+    fn lookup(&self, indices: Option<&Vec<usize>>) -> Vec<&Contract> {
+        indices
+            .map(|indices| indices.iter().map(|&i| &self.contracts[i]).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// What [`ContractArchive::load_cached`] finds at its cache path.
+#[derive(Debug)]
+enum CachedArchive {
+    /// Missing, unreadable or empty: download the archive.
+    Missing,
+    /// Younger than the max age: use it without asking carpetsage.
+    Fresh(ContractArchive),
+    /// Older: ask carpetsage whether it changed, sending the ETag if there is one.
+    Stale(ContractArchive),
+}
+
+impl CachedArchive {
+    /// Reads the copy at `cache_path` along with its ETag; it's fresh if younger than `max_age`.
+    fn read(cache_path: &Path, max_age: Duration) -> Self {
+        if !cache_path.exists() {
+            return Self::Missing;
+        }
+        let mut cached = match ContractArchive::from_path(cache_path) {
+            Ok(cached) if !cached.is_empty() => cached,
+            Ok(_) => return Self::Missing,
+            Err(e) => {
+                warn!("Ignoring unreadable archive cache: {:#}", e);
+                return Self::Missing;
+            }
+        };
+        cached.etag = std::fs::read_to_string(etag_path(cache_path))
+            .ok()
+            .map(|s| s.trim().to_string());
+
+        if cache_age(cache_path).is_some_and(|age| age < max_age) {
+            Self::Fresh(cached)
+        } else {
+            Self::Stale(cached)
+        }
+    }
+}
+
+/// carpetsage's `contracts.json` exactly as it was downloaded, with its ETag.
+#[derive(Debug, Clone)]
+struct Download {
+    json: String,
+    etag: Option<String>,
+}
+
+impl Download {
+    /// Downloads `url`, or returns `None` if it still matches `etag`.
+    async fn fetch(url: &str, etag: Option<&str>) -> Result<Option<Self>> {
+        let mut req = reqwest::Client::new().get(url);
+        if let Some(etag) = etag {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        let res = req.send().await?;
+        if res.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+
+        let res = res.error_for_status()?;
+        let etag = etag_of(&res);
+        Ok(Some(Self {
+            json: res.text().await?,
+            etag,
+        }))
+    }
+
+    fn archive(&self) -> Result<ContractArchive> {
+        let mut archive = ContractArchive::from_json(&self.json)?;
+        archive.etag = self.etag.clone();
+        Ok(archive)
+    }
+
+    /// Writes the download byte for byte, plus the ETag if there is one.
+    ///
+    /// # Details
+    ///
+    /// The body isn't rebuilt from the decoded contracts, which would lose entries that failed to
+    /// decode and fields our protos don't know, and then no longer match the ETag.
+    ///
+    /// The old ETag is removed first and both files are swapped in whole, so an ETag never sits
+    /// next to a copy it doesn't belong to; at worst it's missing and the next refresh downloads
+    /// the archive again.
+    fn save(&self, path: &Path) -> Result<()> {
+        let etag_path = etag_path(path);
+        if etag_path.exists() {
+            std::fs::remove_file(&etag_path)?;
+        }
+        write_atomically(path, self.json.as_bytes()).context(format!(
+            "Cannot write contract archive \"{}\"",
+            path.display()
+        ))?;
+        if let Some(etag) = &self.etag {
+            write_atomically(&etag_path, etag.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// `CONTRACT_ARCHIVE_CACHE` if set, otherwise a file in the system temp dir.
+pub fn default_cache_path() -> PathBuf {
+    std::env::var_os("CONTRACT_ARCHIVE_CACHE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("carpetsage_contracts.json"))
+}
+
+fn decode_contract(proto: &str) -> Result<Contract> {
+    let decoded = BASE64
+        .decode(proto)
+        .context("Cannot base64 decode into byte stream")?;
+    Contract::decode(decoded.as_slice()).context("Cannot decode into `Contract`")
+}
+
+fn etag_of(res: &reqwest::Response) -> Option<String> {
+    res.headers()
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+}
+
+fn etag_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".etag")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut with_suffix = path.as_os_str().to_owned();
+    with_suffix.push(suffix);
+    PathBuf::from(with_suffix)
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it into place, so readers
+/// see either the old file or the whole new one.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let tmp_path = with_suffix(path, ".tmp");
+    std::fs::write(&tmp_path, contents)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+fn cache_age(path: &Path) -> Option<Duration> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contract(id: &str, egg: Egg, season_id: &str, start_time: f64) -> Contract {
+        Contract {
+            identifier: Some(id.to_string()),
+            egg: Some(egg as i32),
+            season_id: (!season_id.is_empty()).then(|| season_id.to_string()),
+            start_time: Some(start_time),
+            ..Default::default()
+        }
+    }
+
+    fn archive() -> ContractArchive {
+        ContractArchive::new(vec![
+            contract("a", Egg::Edible, "winter_2023", 1.0),
+            contract("b", Egg::Superfood, "winter_2023", 2.0),
+            contract("a", Egg::Edible, "", 3.0),
+            contract("c", Egg::Edible, "spring_2024", 4.0),
+        ])
+    }
+
+    fn start_times(contracts: Vec<&Contract>) -> Vec<f64> {
+        contracts.iter().map(|c| c.start_time()).collect()
+    }
+
+    fn to_json(contracts: &[Contract]) -> String {
+        let backed_up: Vec<_> = contracts
+            .iter()
+            .map(|c| CarpetBackedUpContract {
+                id: c.identifier().to_string(),
+                proto: BASE64.encode(c.encode_to_vec()),
+            })
+            .collect();
+        serde_json::to_string(&backed_up).unwrap()
+    }
+
+    /// A path in the temp dir that's removed, along with its ETag, when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "contract-archive-{}-{}.json",
+                name,
+                std::process::id()
+            ));
+            Self(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+            let _ = std::fs::remove_file(etag_path(&self.0));
+        }
+    }
+
+    #[test]
+    fn every_run_of_a_contract_oldest_first() {
+        let archive = archive();
+
+        assert_eq!(start_times(archive.runs("a")), [1.0, 3.0]);
+        assert_eq!(archive.get("a").map(|c| c.start_time()), Some(3.0));
+        assert!(archive.runs("d").is_empty());
+        assert!(archive.get("d").is_none());
+    }
+
+    #[test]
+    fn contracts_by_egg_and_season() {
+        let archive = archive();
+
+        assert_eq!(start_times(archive.with_egg(Egg::Edible)), [1.0, 3.0, 4.0]);
+        assert_eq!(start_times(archive.with_egg(Egg::Superfood)), [2.0]);
+        assert_eq!(start_times(archive.in_season("winter_2023")), [1.0, 2.0]);
+        assert!(archive.in_season("").is_empty());
+    }
+
+    #[test]
+    fn undecodable_contracts_are_skipped() {
+        let json = to_json(&[contract("a", Egg::Edible, "", 1.0)])
+            .replace("}]", "}, {\"id\": \"b\", \"proto\": \"not base64!\"}]");
+        let archive = ContractArchive::from_json(&json).unwrap();
+
+        assert_eq!(archive.len(), 1);
+        assert!(archive.get("a").is_some());
+        assert!(ContractArchive::from_json("{ not json").is_err());
+    }
+
+    #[test]
+    fn downloads_are_saved_as_downloaded() {
+        let path = TempPath::new("save");
+        // Unknown fields and entries we can't decode still have to end up in the cache.
+        let json = to_json(&[contract("a", Egg::Edible, "", 1.0)]).replace(
+            "}]",
+            "}, {\"id\": \"b\", \"proto\": \"not base64!\", \"extra\": 1}]",
+        );
+        let download = Download {
+            json: json.clone(),
+            etag: Some("\"v1\"".to_string()),
+        };
+
+        download.save(&path.0).unwrap();
+        assert_eq!(std::fs::read_to_string(&path.0).unwrap(), json);
+        assert_eq!(
+            std::fs::read_to_string(etag_path(&path.0)).unwrap(),
+            "\"v1\""
+        );
+
+        // A download without an ETag doesn't inherit the old one.
+        Download {
+            etag: None,
+            ..download
+        }
+        .save(&path.0)
+        .unwrap();
+        assert!(!etag_path(&path.0).exists());
+    }
+
+    #[test]
+    fn cache_is_fresh_until_it_ages() {
+        let path = TempPath::new("fresh");
+        let download = Download {
+            json: to_json(&[contract("a", Egg::Edible, "", 1.0)]),
+            etag: Some("\"v1\"".to_string()),
+        };
+        download.save(&path.0).unwrap();
+
+        match CachedArchive::read(&path.0, CACHE_MAX_AGE) {
+            CachedArchive::Fresh(cached) => assert_eq!(cached.etag(), Some("\"v1\"")),
+            other => panic!("expected a fresh cache, got {:?}", other),
+        }
+        match CachedArchive::read(&path.0, Duration::ZERO) {
+            CachedArchive::Stale(cached) => assert_eq!(cached.etag(), Some("\"v1\"")),
+            other => panic!("expected a stale cache, got {:?}", other),
+        }
+
+        std::fs::remove_file(etag_path(&path.0)).unwrap();
+        match CachedArchive::read(&path.0, Duration::ZERO) {
+            CachedArchive::Stale(cached) => assert_eq!(cached.etag(), None),
+            other => panic!("expected a stale cache, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_empty_or_corrupt_cache_is_downloaded_again() {
+        let path = TempPath::new("missing");
+        assert!(matches!(
+            CachedArchive::read(&path.0, CACHE_MAX_AGE),
+            CachedArchive::Missing
+        ));
+
+        for json in ["[]", "{ not json"] {
+            std::fs::write(&path.0, json).unwrap();
+            std::fs::write(etag_path(&path.0), "\"v1\"").unwrap();
+            assert!(matches!(
+                CachedArchive::read(&path.0, CACHE_MAX_AGE),
+                CachedArchive::Missing
+            ));
+        }
+    }
+}
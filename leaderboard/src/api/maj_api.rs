@@ -1,14 +1,24 @@
+use std::collections::HashMap;
+
 use crate::{contracts::coop_flag::CoopFlag, error};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use dotenvy_macro::dotenv;
-use serde::Deserialize;
+use log::debug;
+use serde::{Deserialize, Deserializer};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
 
-const MAJ_COOPS_API_URL: &str = dotenv!("MAJ_API");
+pub(crate) const MAJ_COOPS_API_URL: &str = dotenv!("MAJ_API");
 
+/// One contract's entry in Maj's response.
 #[derive(Debug, Clone, Deserialize)]
 pub struct MajCoopsResponse {
-    #[serde(rename = "startTime")]
-    pub start_time: String,
+    /// Only present on some responses. Maj can leave contracts out or reorder them, so
+    /// [`get_maj_api_responses_from`] looks contracts without it up one at a time.
+    #[serde(rename = "contract", default)]
+    pub contract_id: Option<String>,
+    #[serde(rename = "startTime", deserialize_with = "deserialize_start_time")]
+    pub start_time: OffsetDateTime,
     #[serde(rename = "activeCoops")]
     pub active_coops: bool,
     pub coops: Vec<MajCoops>,
@@ -17,14 +27,176 @@ pub struct MajCoopsResponse {
 #[derive(Debug, Clone, Deserialize)]
 pub struct MajCoops {
     #[serde(rename = "coopFlags")]
-    pub coop_flags: CoopFlag,
+    pub coop_flags: MajCoopFlags,
     pub code: String,
 }
 
+/// Every flag Maj can set on a coop. A coop can have several, see [`MajCoopFlags::coop_flag`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MajCoopFlags {
+    pub any_grade: bool,
+    pub carry: bool,
+    pub fast_run: bool,
+    pub speed_run: bool,
+}
+
+impl MajCoops {
+    pub fn new(code: impl Into<String>, coop_flags: MajCoopFlags) -> Self {
+        Self {
+            coop_flags,
+            code: code.into(),
+        }
+    }
+}
+
+impl MajCoopFlags {
+    /// The one flag the coop is listed under: speedrun over fastrun over carry over any grade.
+    pub fn coop_flag(&self) -> CoopFlag {
+        if self.speed_run {
+            CoopFlag::Speedrun
+        } else if self.fast_run {
+            CoopFlag::Fastrun
+        } else if self.carry {
+            CoopFlag::Carry
+        } else if self.any_grade {
+            CoopFlag::AnyGrade
+        } else {
+            CoopFlag::NoFlags
+        }
+    }
+}
+
+impl From<CoopFlag> for MajCoopFlags {
+    fn from(coop_flag: CoopFlag) -> Self {
+        Self {
+            any_grade: coop_flag == CoopFlag::AnyGrade,
+            carry: coop_flag == CoopFlag::Carry,
+            fast_run: coop_flag == CoopFlag::Fastrun,
+            speed_run: coop_flag == CoopFlag::Speedrun,
+        }
+    }
+}
+
+impl MajCoopsResponse {
+    pub fn coop_codes(&self, coop_flag: CoopFlag) -> Vec<String> {
+        if !self.active_coops {
+            return Vec::new();
+        }
+        self.coops
+            .iter()
+            .filter(|&c| c.coop_flags.coop_flag() == coop_flag)
+            .map(|c| c.code.clone())
+            .collect()
+    }
+}
+
+/// `startTime` has shown up both as an RFC 3339 string and as unix seconds (sometimes quoted),
+/// so accept all of them.
+fn deserialize_start_time<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawStartTime {
+        Seconds(f64),
+        Text(String),
+    }
+
+    let secs = match RawStartTime::deserialize(deserializer)? {
+        RawStartTime::Seconds(secs) => secs,
+        RawStartTime::Text(text) => match text.trim().parse::<f64>() {
+            Ok(secs) => secs,
+            Err(_) => {
+                return OffsetDateTime::parse(text.trim(), &Rfc3339)
+                    .map_err(serde::de::Error::custom)
+            }
+        },
+    };
+    OffsetDateTime::from_unix_timestamp(secs as i64).map_err(serde::de::Error::custom)
+}
+
 pub async fn get_maj_api_response(contract_id: impl Into<String>) -> Result<MajCoopsResponse> {
-    let url = format!("{}?contract={}", MAJ_COOPS_API_URL, contract_id.into());
+    get_maj_api_response_from(MAJ_COOPS_API_URL, contract_id).await
+}
+
+pub async fn get_maj_api_response_from(
+    api_url: &str,
+    contract_id: impl Into<String>,
+) -> Result<MajCoopsResponse> {
+    // With a single contract requested, there's no doubt which one the response is for.
+    let maj_res = fetch_maj_api_responses(api_url, &[contract_id.into()])
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(error::EmptyMajResponse)?;
+    Ok(maj_res)
+}
+
+pub async fn get_maj_api_responses(
+    contract_ids: &[String],
+) -> Result<HashMap<String, MajCoopsResponse>> {
+    get_maj_api_responses_from(MAJ_COOPS_API_URL, contract_ids).await
+}
+
+/// Queries every contract in `contract_ids` in one request, keyed by the contract ID Maj returns.
+///
+/// # Details
+///
+/// If any response comes back without its contract ID, there's no telling which contract it
+/// belongs to, so every contract not otherwise accounted for is requested on its own. Contracts
+/// Maj has nothing for are left out.
+pub async fn get_maj_api_responses_from(
+    api_url: &str,
+    contract_ids: &[String],
+) -> Result<HashMap<String, MajCoopsResponse>> {
+    let mut by_id = HashMap::new();
+    let mut unlabeled_count = 0;
+    for res in fetch_maj_api_responses(api_url, contract_ids).await? {
+        match res.contract_id.clone() {
+            Some(id) => {
+                by_id.insert(id, res);
+            }
+            None => unlabeled_count += 1,
+        }
+    }
+    if unlabeled_count == 0 {
+        return Ok(by_id);
+    }
+
+    debug!(
+        "{} Maj responses without a contract ID, requesting contracts one by one",
+        unlabeled_count
+    );
+    for id in contract_ids {
+        if by_id.contains_key(id) {
+            continue;
+        }
+        // With a single contract requested, there's no doubt which one the response is for.
+        let res = fetch_maj_api_responses(api_url, std::slice::from_ref(id))
+            .await?
+            .into_iter()
+            .next();
+        if let Some(res) = res {
+            by_id.insert(id.clone(), res);
+        }
+    }
+    Ok(by_id)
+}
+
+async fn fetch_maj_api_responses(
+    api_url: &str,
+    contract_ids: &[String],
+) -> Result<Vec<MajCoopsResponse>> {
+    if contract_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let query: Vec<_> = contract_ids.iter().map(|id| ("contract", id)).collect();
     let maj_coop_json = reqwest::Client::new()
-        .get(url)
+        .get(api_url)
+        .query(&query)
         .header(
             "User-Agent",
             "Rust backend test bot by @integerrrr on discord",
@@ -34,28 +206,177 @@ pub async fn get_maj_api_response(contract_id: impl Into<String>) -> Result<MajC
         .text()
         .await?;
 
-    let maj_res: Vec<MajCoopsResponse> = serde_json::from_str(&maj_coop_json)?;
-    let maj_res = maj_res
-        // because we're only ever supplying 1 contract ID at a time
-        // trying to keep things simple here
-        .first()
-        .cloned()
-        .ok_or(error::EmptyMajResponse)?;
-    Ok(maj_res)
+    serde_json::from_str(&maj_coop_json).context("Cannot parse Maj API response")
 }
 
 pub async fn get_maj_active_coop_codes(
     contract_id: impl Into<String>,
     coop_flag: CoopFlag,
 ) -> Result<Vec<String>> {
-    let res = get_maj_api_response(contract_id).await?;
-    if !res.active_coops {
-        return Ok(Vec::new());
-    }
+    get_maj_active_coop_codes_from(MAJ_COOPS_API_URL, contract_id, coop_flag).await
+}
+
+pub async fn get_maj_active_coop_codes_from(
+    api_url: &str,
+    contract_id: impl Into<String>,
+    coop_flag: CoopFlag,
+) -> Result<Vec<String>> {
+    let res = get_maj_api_response_from(api_url, contract_id).await?;
+    Ok(res.coop_codes(coop_flag))
+}
+
+pub async fn get_maj_active_coop_codes_batch_from(
+    api_url: &str,
+    contract_ids: &[String],
+    coop_flag: CoopFlag,
+) -> Result<HashMap<String, Vec<String>>> {
+    let res = get_maj_api_responses_from(api_url, contract_ids).await?;
     Ok(res
-        .coops
         .iter()
-        .filter(|&c| c.coop_flags == coop_flag)
-        .map(|c| c.code.clone())
+        .map(|(id, r)| (id.clone(), r.coop_codes(coop_flag)))
         .collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    type Requests = Arc<Mutex<Vec<Vec<String>>>>;
+
+    /// Answers every request on a local port with `respond(requested contract IDs)`, and logs
+    /// which contracts each request asked for.
+    async fn serve(respond: fn(&[String]) -> String) -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/coops", listener.local_addr().unwrap());
+        let requests = Requests::default();
+
+        let log = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+
+                let request = String::from_utf8_lossy(&request);
+                let path = request.split_whitespace().nth(1).unwrap_or_default();
+                let ids: Vec<_> = path
+                    .split(['?', '&'])
+                    .filter_map(|p| p.strip_prefix("contract="))
+                    .map(str::to_string)
+                    .collect();
+                let body = respond(&ids);
+                log.lock().unwrap().push(ids);
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                    Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    /// A response with one speedrun coop, `<contract ID>-coop`.
+    fn response(contract_id: &str, labeled: bool) -> String {
+        let label = if labeled {
+            format!("\"contract\": \"{}\", ", contract_id)
+        } else {
+            String::new()
+        };
+        format!(
+            "{{ {}\"startTime\": \"1700000000\", \"activeCoops\": true, \
+            \"coops\": [ {{ \"code\": \"{}-coop\", \"coopFlags\": {{ \"speedRun\": true }} }} ] }}",
+            label, contract_id
+        )
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    async fn coop_codes(api_url: &str, contract_ids: &[String]) -> Vec<(String, Vec<String>)> {
+        let mut coop_codes: Vec<_> =
+            get_maj_active_coop_codes_batch_from(api_url, contract_ids, CoopFlag::Speedrun)
+                .await
+                .unwrap()
+                .into_iter()
+                .collect();
+        coop_codes.sort();
+        coop_codes
+    }
+
+    #[tokio::test]
+    async fn responses_are_keyed_by_their_contract_id() {
+        // Reordered, but every response says which contract it's for.
+        let (url, requests) =
+            serve(|_| format!("[{}, {}]", response("b", true), response("a", true))).await;
+
+        assert_eq!(
+            coop_codes(&url, &ids(&["a", "b"])).await,
+            [
+                ("a".to_string(), ids(&["a-coop"])),
+                ("b".to_string(), ids(&["b-coop"]))
+            ]
+        );
+        assert_eq!(*requests.lock().unwrap(), [ids(&["a", "b"])]);
+    }
+
+    #[tokio::test]
+    async fn contracts_without_an_id_are_requested_one_by_one() {
+        let (url, requests) = serve(|ids| match ids {
+            [id] if id == "c" => "[]".to_string(),
+            [id] => format!("[{}]", response(id, false)),
+            _ => format!("[{}, {}]", response("b", false), response("a", true)),
+        })
+        .await;
+
+        assert_eq!(
+            coop_codes(&url, &ids(&["a", "b", "c"])).await,
+            [
+                ("a".to_string(), ids(&["a-coop"])),
+                ("b".to_string(), ids(&["b-coop"]))
+            ]
+        );
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [ids(&["a", "b", "c"]), ids(&["b"]), ids(&["c"])]
+        );
+    }
+
+    #[test]
+    fn coop_flags_are_listed_under_the_strongest_one() {
+        let coops: Vec<MajCoops> = serde_json::from_str(
+            r#"[
+                { "code": "a", "coopFlags": { "anyGrade": true, "speedRun": true } },
+                { "code": "b", "coopFlags": { "carry": true, "fastRun": true } },
+                { "code": "c", "coopFlags": {} }
+            ]"#,
+        )
+        .unwrap();
+
+        let flags: Vec<_> = coops.iter().map(|c| c.coop_flags.coop_flag()).collect();
+        assert_eq!(
+            flags,
+            [CoopFlag::Speedrun, CoopFlag::Fastrun, CoopFlag::NoFlags]
+        );
+        assert_eq!(
+            MajCoopFlags::from(CoopFlag::Carry).coop_flag(),
+            CoopFlag::Carry
+        );
+    }
+}
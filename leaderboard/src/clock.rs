@@ -0,0 +1,64 @@
+use std::fmt::Debug;
+
+use anyhow::Result;
+use time::OffsetDateTime;
+
+/// Where "now" comes from.
+///
+/// # Details
+///
+/// Everything that asks for the current time (predictions, timestamps, table headers, picking
+/// the recent contracts) goes through a `Clock`, so a [`FixedClock`] renders the same leaderboard
+/// every time, e.g. to look at a historical board or to compare against a golden output.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> OffsetDateTime;
+
+    fn unix_timestamp(&self) -> i64 {
+        self.now().unix_timestamp()
+    }
+}
+
+/// The system's wall clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+/// A clock stuck at one instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub OffsetDateTime);
+
+impl FixedClock {
+    pub fn from_unix_timestamp(timestamp: i64) -> Result<Self> {
+        Ok(Self(OffsetDateTime::from_unix_timestamp(timestamp)?))
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> OffsetDateTime {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formatter::discord_timestamp::DiscordTimestamp;
+
+    use super::*;
+
+    #[test]
+    fn fixed_clock_stays_put() {
+        let clock = FixedClock::from_unix_timestamp(1_700_000_000).unwrap();
+
+        assert_eq!(clock.unix_timestamp(), 1_700_000_000);
+        assert_eq!(
+            DiscordTimestamp::new_from_secs_remaining_with(60, &clock).unix_timestamp(),
+            1_700_000_060
+        );
+        assert!(FixedClock::from_unix_timestamp(i64::MAX).is_err());
+    }
+}
@@ -1,3 +1,12 @@
 pub mod active_contract;
+pub mod boosts;
+pub mod contract_resolver;
+pub mod contract_summary;
 pub mod coop;
 pub mod coop_flag;
+pub mod coop_metrics;
+pub mod coop_source;
+pub mod farm_simulation;
+pub mod forecast;
+pub mod goal_timeline;
+pub mod modifiers;
@@ -1,23 +1,34 @@
 use std::fmt::{Display, Formatter};
 use std::slice::Iter;
+use std::sync::Arc;
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use log::error;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use ei::ei::Contract;
+use ei::ei::{Contract, Egg};
 
-use crate::api::{get_backup_contracts, get_periodicals, maj_api};
+use crate::clock::{Clock, SystemClock};
 
+use super::boosts::BoostCatalog;
+use super::contract_resolver::ContractResolverChain;
+use super::contract_summary::ContractSummary;
 use super::coop::{Coop, CoopBuilder};
 use super::coop_flag::CoopFlag;
+use super::coop_metrics::CoopMetrics;
+use super::coop_source::{CoopSource, MajCoopSource};
+use super::forecast::ForecastConfig;
 
 #[derive(Debug, Error, Clone)]
 pub struct ActiveContract {
     contract: Contract,
     coop_flag: CoopFlag,
     coops: Vec<Coop>,
+    failed_coop_codes: Vec<String>,
+    boost_catalog: Arc<BoostCatalog>,
+    forecast_config: Option<ForecastConfig>,
+    clock: Arc<dyn Clock>,
 }
 
 impl ActiveContract {
@@ -26,39 +37,121 @@ impl ActiveContract {
             contract,
             coop_flag,
             coops: vec![],
+            failed_coop_codes: vec![],
+            boost_catalog: Arc::default(),
+            forecast_config: None,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// The clock coops are measured against, the system clock by default.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Forecasts every coop filled from now on with `config`; none are forecast by default.
+    pub fn set_forecast_config(&mut self, config: Option<ForecastConfig>) {
+        self.forecast_config = config;
+    }
+
+    /// The boost catalog handed to every coop filled from now on.
+    pub fn set_boost_catalog(&mut self, boost_catalog: Arc<BoostCatalog>) {
+        self.boost_catalog = boost_catalog;
+    }
+
     pub async fn fill_coops(&mut self) -> Result<()> {
-        let coop_codes =
-            maj_api::get_maj_active_coop_codes(self.contract.identifier(), self.coop_flag).await?;
+        self.fill_coops_from(&MajCoopSource::new()).await
+    }
+
+    pub async fn fill_coops_from(&mut self, source: &dyn CoopSource) -> Result<()> {
+        let coop_codes = source
+            .coop_codes(self.contract.identifier(), self.coop_flag)
+            .await?;
+        self.fill_coops_with_codes(coop_codes).await
+    }
+
+    /// Fills the coops from codes that were already looked up, e.g. with
+    /// [`CoopSource::coop_codes_batch`] for several contracts at once.
+    pub async fn fill_coops_with_codes(&mut self, coop_codes: Vec<String>) -> Result<()> {
         for code in coop_codes {
             let new = match CoopBuilder::new()
                 .with_contract(self.contract.clone())
                 .with_coop_code(code.clone())
-                .build()
+                .build_with_clock(self.clock.as_ref())
                 .await
             {
                 Ok(coop) => coop,
-                Err(_) => {
-                    error!("Invalid coop code: \"{}\"", code.clone());
+                Err(e) => {
+                    error!("Skipping coop \"{}\": {:#}", code, e);
+                    self.failed_coop_codes.push(code);
                     continue;
                 }
             };
             self.coops.push(new);
         }
+
+        // Predictions are anchored to each response's own timestamp; measure every coop's data
+        // age from the same instant.
+        let reference_time = self.clock.unix_timestamp();
+        self.coops.iter_mut().for_each(|c| {
+            c.set_boost_catalog(self.boost_catalog.clone());
+            c.refresh_metrics_at(reference_time);
+            if let Some(config) = &self.forecast_config {
+                c.refresh_forecast(config);
+            }
+        });
         self.coops.sort();
         Ok(())
     }
 
+    pub fn contract_id(&self) -> &str {
+        self.contract.identifier()
+    }
+
+    pub fn coop_flag(&self) -> CoopFlag {
+        self.coop_flag
+    }
+
     pub fn contract_name(&self) -> &str {
         self.contract.name()
     }
 
+    /// The contract's egg, e.g. "Rocket Fuel".
+    pub fn egg_name(&self) -> String {
+        match self.contract.egg() {
+            Egg::Ai => "AI".to_string(),
+            egg => egg
+                .as_str_name()
+                .split('_')
+                .map(|word| word[..1].to_string() + &word[1..].to_lowercase())
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+
     pub fn coops(&self) -> Iter<'_, Coop> {
         self.coops.iter()
     }
 
+    /// Hands the coops over, e.g. to a [`SrucTable`](crate::report_generator::sruc::SrucTable),
+    /// without copying them.
+    pub fn into_coops(self) -> Vec<Coop> {
+        self.coops
+    }
+
+    /// Codes of the coops that couldn't be loaded, e.g. because the code was wrong.
+    pub fn failed_coop_codes(&self) -> &[String] {
+        &self.failed_coop_codes
+    }
+
+    pub fn coop_metrics(&self) -> Vec<&CoopMetrics> {
+        self.coops().map(|c| c.metrics()).collect()
+    }
+
+    pub fn summary(&self) -> ContractSummary {
+        ContractSummary::from_metrics(&self.coop_metrics())
+    }
+
     pub fn all_coops_green_scrolled(&self) -> bool {
         self.coops().all(|c| c.green_scrolled())
     }
@@ -76,6 +169,8 @@ pub struct NoContractId;
 #[derive(Debug, Clone, Default)]
 pub struct ContractId(String);
 #[derive(Debug, Clone, Default)]
+pub struct PreloadedContract(Contract);
+#[derive(Debug, Clone, Default)]
 pub struct CoopFlagNotSpecified;
 #[derive(Debug, Clone, Default)]
 pub struct CoopFlagSpecified(CoopFlag);
@@ -104,6 +199,13 @@ impl<I, F> ActiveContractBuilder<I, F> {
         }
     }
 
+    pub fn with_contract(self, contract: Contract) -> ActiveContractBuilder<PreloadedContract, F> {
+        ActiveContractBuilder {
+            contract_id: PreloadedContract(contract),
+            coop_flag: self.coop_flag,
+        }
+    }
+
     pub fn with_coop_flag(
         self,
         coop_flag: CoopFlag,
@@ -117,21 +219,17 @@ impl<I, F> ActiveContractBuilder<I, F> {
 
 impl ActiveContractBuilder<ContractId, CoopFlagSpecified> {
     pub async fn build(self) -> Result<ActiveContract> {
-        let periodicals_response = get_periodicals().await?;
-        let contracts_response = periodicals_response
-            .contracts
-            .context("No ContractsResponse found")?;
-        if let Some(contract) = contracts_response
-            .contracts
-            .iter()
-            .find(|&c| c.identifier() == self.contract_id.0)
-        {
-            return Ok(ActiveContract::new(contract.clone(), self.coop_flag.0));
-        }
+        self.build_with(&ContractResolverChain::standard()).await
+    }
 
-        match get_backup_contracts(&self.contract_id.0).await {
-            Ok(c) => Ok(ActiveContract::new(c.clone(), self.coop_flag.0)),
-            Err(e) => Err(e),
-        }
+    pub async fn build_with(self, resolvers: &ContractResolverChain) -> Result<ActiveContract> {
+        let contract = resolvers.resolve(&self.contract_id.0).await?;
+        Ok(ActiveContract::new(contract, self.coop_flag.0))
+    }
+}
+
+impl ActiveContractBuilder<PreloadedContract, CoopFlagSpecified> {
+    pub fn build(self) -> ActiveContract {
+        ActiveContract::new(self.contract_id.0, self.coop_flag.0)
     }
 }
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use log::debug;
+
+use ei::ei::backup::ActiveBoost;
+use ei::ei::live_config::BoostsConfig;
+
+/// What a boost does to egg production, as far as the leaderboard cares.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoostEffect {
+    /// Tachyon prisms, multiplying the internal hatchery rate.
+    HatcheryRate(f64),
+    /// Boost beacons, multiplying the effect of every other running boost.
+    BoostMultiplier(f64),
+    /// Earnings, soul eggs, ... nothing that changes how fast eggs are shipped.
+    Other,
+}
+
+/// Built-in effects of the boosts that matter for contracts.
+const BUILTIN_BOOSTS: &[(&str, BoostEffect)] = &[
+    ("tachyon_prism_orange", BoostEffect::HatcheryRate(10.0)),
+    ("tachyon_prism_orange_big", BoostEffect::HatcheryRate(10.0)),
+    ("tachyon_prism_blue", BoostEffect::HatcheryRate(100.0)),
+    ("tachyon_prism_blue_big", BoostEffect::HatcheryRate(100.0)),
+    ("tachyon_prism_purple", BoostEffect::HatcheryRate(1000.0)),
+    (
+        "tachyon_prism_purple_big",
+        BoostEffect::HatcheryRate(1000.0),
+    ),
+    ("boost_beacon_blue", BoostEffect::BoostMultiplier(2.0)),
+    ("boost_beacon_blue_big", BoostEffect::BoostMultiplier(5.0)),
+    ("boost_beacon_purple", BoostEffect::BoostMultiplier(5.0)),
+    ("boost_beacon_orange", BoostEffect::BoostMultiplier(10.0)),
+];
+
+/// Looks up what each boost ID does.
+///
+/// # Details
+///
+/// `LiveConfig.BoostsConfig` only says which boosts exist and what they cost, not what they do,
+/// so effects always come from [`BUILTIN_BOOSTS`]. Unknown boosts count as [`BoostEffect::Other`].
+#[derive(Debug, Clone)]
+pub struct BoostCatalog {
+    effects: HashMap<String, BoostEffect>,
+}
+
+impl BoostCatalog {
+    pub fn builtin() -> Self {
+        Self {
+            effects: BUILTIN_BOOSTS
+                .iter()
+                .map(|&(id, effect)| (id.to_string(), effect))
+                .collect(),
+        }
+    }
+
+    /// The built-in boosts, extended with any the live config lists that we don't know yet.
+    /// Built-in boosts missing from the live config keep their effect.
+    pub fn from_live_config(config: &BoostsConfig) -> Self {
+        let mut catalog = Self::builtin();
+        for item in &config.item_configs {
+            if !catalog.effects.contains_key(item.boost_id()) {
+                debug!(
+                    "No production effect known for boost \"{}\"",
+                    item.boost_id()
+                );
+                catalog = catalog.with_effect(item.boost_id(), BoostEffect::Other);
+            }
+        }
+        catalog
+    }
+
+    pub fn with_effect(mut self, boost_id: impl Into<String>, effect: BoostEffect) -> Self {
+        self.effects.insert(boost_id.into(), effect);
+        self
+    }
+
+    pub fn effect(&self, boost_id: &str) -> BoostEffect {
+        self.effects
+            .get(boost_id)
+            .copied()
+            .unwrap_or(BoostEffect::Other)
+    }
+
+    /// The IHR multiplier of a set of boosts running together.
+    ///
+    /// # Details
+    ///
+    /// Prisms add up, and beacons (which add up too) multiply the prisms' total. Beacons on their
+    /// own do nothing.
+    pub fn hatchery_multiplier<'a>(&self, boost_ids: impl IntoIterator<Item = &'a str>) -> f64 {
+        let (mut prisms, mut beacons) = (0f64, 0f64);
+        for id in boost_ids {
+            match self.effect(id) {
+                BoostEffect::HatcheryRate(m) => prisms += m,
+                BoostEffect::BoostMultiplier(m) => beacons += m,
+                BoostEffect::Other => {}
+            }
+        }
+        (prisms * beacons.max(1.0)).max(1.0)
+    }
+
+    /// How a farm's IHR changes as its running boosts expire, as `(seconds from now, new IHR)`
+    /// for every expiry that changes it, soonest first.
+    pub fn hatchery_schedule(&self, ihr: f64, boosts: &[ActiveBoost]) -> Vec<(f64, f64)> {
+        let mut running: Vec<_> = boosts.iter().filter(|b| b.time_remaining() > 0.0).collect();
+        running.sort_by(|a, b| a.time_remaining().total_cmp(&b.time_remaining()));
+
+        let multiplier_of = |running: &[&ActiveBoost]| {
+            self.hatchery_multiplier(running.iter().map(|b| b.boost_id()))
+        };
+        let base_ihr = ihr / multiplier_of(&running);
+
+        let mut changes = vec![];
+        let mut current = ihr;
+        for i in 0..running.len() {
+            let next = base_ihr * multiplier_of(&running[i + 1..]);
+            if next != current {
+                changes.push((running[i].time_remaining(), next));
+                current = next;
+            }
+        }
+        changes
+    }
+}
+
+impl Default for BoostCatalog {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ei::ei::live_config::boosts_config::ItemConfig;
+
+    use super::*;
+
+    fn active_boost(id: &str, time_remaining: f64) -> ActiveBoost {
+        ActiveBoost {
+            boost_id: Some(id.to_string()),
+            time_remaining: Some(time_remaining),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn live_config_keeps_builtin_boosts() {
+        let config = BoostsConfig {
+            item_configs: vec![ItemConfig {
+                boost_id: Some("jimbos_blue".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let catalog = BoostCatalog::from_live_config(&config);
+
+        assert_eq!(
+            catalog.effect("tachyon_prism_blue"),
+            BoostEffect::HatcheryRate(100.0)
+        );
+        assert_eq!(catalog.effect("jimbos_blue"), BoostEffect::Other);
+    }
+
+    #[test]
+    fn beacons_multiply_prisms() {
+        let catalog = BoostCatalog::builtin();
+
+        assert_eq!(catalog.hatchery_multiplier([]), 1.0);
+        assert_eq!(catalog.hatchery_multiplier(["boost_beacon_blue"]), 1.0);
+        assert_eq!(
+            catalog.hatchery_multiplier(["tachyon_prism_orange", "tachyon_prism_blue"]),
+            110.0
+        );
+        assert_eq!(
+            catalog.hatchery_multiplier(["tachyon_prism_blue", "boost_beacon_blue_big"]),
+            500.0
+        );
+    }
+
+    #[test]
+    fn schedule_drops_to_the_base_rate() {
+        let catalog = BoostCatalog::builtin();
+        let boosts = [
+            active_boost("tachyon_prism_blue", 600.0),
+            active_boost("boost_beacon_blue", 300.0),
+            active_boost("jimbos_blue", 100.0),
+        ];
+
+        // 2 chickens/s base rate, ×100 from the prism, ×2 from the beacon.
+        assert_eq!(
+            catalog.hatchery_schedule(400.0, &boosts),
+            vec![(300.0, 200.0), (600.0, 2.0)]
+        );
+        assert!(catalog.hatchery_schedule(2.0, &[]).is_empty());
+    }
+}
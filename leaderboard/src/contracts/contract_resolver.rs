@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::{Context, Error, Result};
+use log::{debug, info, warn};
+use prost::Message;
+use sqlx::postgres::PgPool;
+use tokio::sync::OnceCell;
+
+use ei::ei::Contract;
+
+use crate::api::contract_archive::{self, ContractArchive};
+use crate::api::get_periodicals;
+use crate::error::InvalidContractId;
+
+/// `Ok(None)` means the resolver doesn't know the contract, `Err` means it couldn't check.
+pub type ContractFuture<'a> = Pin<Box<dyn Future<Output = Result<Option<Contract>>> + Send + 'a>>;
+
+/// One place a `Contract` can be looked up from.
+pub trait ContractResolver: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn resolve<'a>(&'a self, contract_id: &'a str) -> ContractFuture<'a>;
+}
+
+/// Contracts already resolved during this run.
+#[derive(Debug, Default)]
+pub struct MemoryCacheResolver {
+    contracts: Mutex<HashMap<String, Contract>>,
+}
+
+impl MemoryCacheResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, contract: Contract) {
+        self.contracts
+            .lock()
+            .expect("contract cache lock poisoned")
+            .insert(contract.identifier().to_string(), contract);
+    }
+}
+
+impl ContractResolver for MemoryCacheResolver {
+    fn name(&self) -> &str {
+        "memory cache"
+    }
+
+    fn resolve<'a>(&'a self, contract_id: &'a str) -> ContractFuture<'a> {
+        let contract = self
+            .contracts
+            .lock()
+            .expect("contract cache lock poisoned")
+            .get(contract_id)
+            .cloned();
+        Box::pin(async { Ok(contract) })
+    }
+}
+
+/// Contracts currently offered in-game.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PeriodicalsResolver;
+
+impl ContractResolver for PeriodicalsResolver {
+    fn name(&self) -> &str {
+        "periodicals"
+    }
+
+    fn resolve<'a>(&'a self, contract_id: &'a str) -> ContractFuture<'a> {
+        Box::pin(async move {
+            let contracts_response = get_periodicals()
+                .await?
+                .contracts
+                .context("No ContractsResponse found")?;
+            Ok(contracts_response
+                .contracts
+                .into_iter()
+                .find(|c| c.identifier() == contract_id))
+        })
+    }
+}
+
+/// A carpetsage-format archive on disk, read the first time it's needed.
+#[derive(Debug)]
+pub struct ArchiveFileResolver {
+    path: PathBuf,
+    archive: OnceCell<ContractArchive>,
+}
+
+impl ArchiveFileResolver {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            archive: OnceCell::new(),
+        }
+    }
+}
+
+impl ContractResolver for ArchiveFileResolver {
+    fn name(&self) -> &str {
+        "archive file"
+    }
+
+    fn resolve<'a>(&'a self, contract_id: &'a str) -> ContractFuture<'a> {
+        Box::pin(async move {
+            if !self.path.exists() {
+                return Ok(None);
+            }
+            let archive = self
+                .archive
+                .get_or_try_init(|| async { ContractArchive::from_path(&self.path) })
+                .await?;
+            Ok(archive.get(contract_id).cloned())
+        })
+    }
+}
+
+/// Contracts stored as encoded protos in the `contracts` table.
+#[derive(Debug, Clone)]
+pub struct DatabaseResolver {
+    pool: PgPool,
+}
+
+impl DatabaseResolver {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let pool = PgPool::connect(database_url)
+            .await
+            .context("Cannot connect to the contracts database")?;
+        Ok(Self::new(pool))
+    }
+
+    pub async fn create_table(&self) -> Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS contracts (
+                identifier TEXT PRIMARY KEY,
+                proto BYTEA NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn store(&self, contract: &Contract) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO contracts (identifier, proto) VALUES ($1, $2)
+            ON CONFLICT (identifier) DO UPDATE SET proto = EXCLUDED.proto",
+        )
+        .bind(contract.identifier())
+        .bind(contract.encode_to_vec())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+impl ContractResolver for DatabaseResolver {
+    fn name(&self) -> &str {
+        "database"
+    }
+
+    fn resolve<'a>(&'a self, contract_id: &'a str) -> ContractFuture<'a> {
+        Box::pin(async move {
+            let proto: Option<Vec<u8>> =
+                sqlx::query_scalar("SELECT proto FROM contracts WHERE identifier = $1")
+                    .bind(contract_id)
+                    .fetch_optional(&self.pool)
+                    .await?;
+            proto
+                .map(|p| Contract::decode(p.as_slice()).context("Cannot decode into `Contract`"))
+                .transpose()
+        })
+    }
+}
+
+/// The carpetsage archive on GitHub, shared through [`ContractArchive::shared`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CarpetsageResolver;
+
+impl ContractResolver for CarpetsageResolver {
+    fn name(&self) -> &str {
+        "carpetsage"
+    }
+
+    fn resolve<'a>(&'a self, contract_id: &'a str) -> ContractFuture<'a> {
+        Box::pin(async move { Ok(ContractArchive::shared().await?.get(contract_id).cloned()) })
+    }
+}
+
+/// Tries each resolver in turn until one knows the contract.
+///
+/// # Details
+///
+/// The chain always checks its own [`MemoryCacheResolver`] first and remembers whatever the other
+/// resolvers find, so resolving the same contract twice is free. A resolver that errors is logged
+/// and skipped.
+#[derive(Default)]
+pub struct ContractResolverChain {
+    cache: MemoryCacheResolver,
+    resolvers: Vec<Box<dyn ContractResolver>>,
+}
+
+impl ContractResolverChain {
+    /// A chain with nothing but the memory cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Periodicals, then the local archive file, then carpetsage.
+    pub fn standard() -> Self {
+        Self::new()
+            .with_resolver(PeriodicalsResolver)
+            .with_resolver(ArchiveFileResolver::new(
+                contract_archive::default_cache_path(),
+            ))
+            .with_resolver(CarpetsageResolver)
+    }
+
+    pub fn with_resolver(mut self, resolver: impl ContractResolver + 'static) -> Self {
+        self.resolvers.push(Box::new(resolver));
+        self
+    }
+
+    /// Inserts `resolver` before the first resolver named `before`, or at the end if there is none.
+    pub fn with_resolver_before(
+        mut self,
+        before: &str,
+        resolver: impl ContractResolver + 'static,
+    ) -> Self {
+        let index = self
+            .resolvers
+            .iter()
+            .position(|r| r.name() == before)
+            .unwrap_or(self.resolvers.len());
+        self.resolvers.insert(index, Box::new(resolver));
+        self
+    }
+
+    pub fn preload(&self, contract: Contract) {
+        self.cache.insert(contract);
+    }
+
+    pub async fn resolve(&self, contract_id: &str) -> Result<Contract> {
+        let resolvers = std::iter::once(&self.cache as &dyn ContractResolver)
+            .chain(self.resolvers.iter().map(|r| r.as_ref()));
+
+        for resolver in resolvers {
+            let start = Instant::now();
+            match resolver.resolve(contract_id).await {
+                Ok(Some(contract)) => {
+                    info!(
+                        "Resolved \"{}\" from {} in {:?}",
+                        contract_id,
+                        resolver.name(),
+                        start.elapsed()
+                    );
+                    self.cache.insert(contract.clone());
+                    return Ok(contract);
+                }
+                Ok(None) => debug!(
+                    "\"{}\" not found in {} ({:?})",
+                    contract_id,
+                    resolver.name(),
+                    start.elapsed()
+                ),
+                Err(e) => warn!(
+                    "{} failed to resolve \"{}\" after {:?}: {:#}",
+                    resolver.name(),
+                    contract_id,
+                    start.elapsed(),
+                    e
+                ),
+            }
+        }
+
+        Err(Error::from(InvalidContractId)).context(format!(": \"{}\"", contract_id))
+    }
+}
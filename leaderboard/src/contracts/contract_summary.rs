@@ -0,0 +1,101 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::formatter::discord_table::Aggregate;
+use crate::formatter::duration::Duration;
+
+use super::coop_metrics::CoopMetrics;
+
+/// An overview of a contract's coops.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ContractSummary {
+    pub coop_count: usize,
+    /// Coops that shipped their final goal, counting offline eggs.
+    pub finished_count: usize,
+    /// `None` without any coops.
+    pub fastest_duration: Option<Duration>,
+    pub median_duration: Option<Duration>,
+}
+
+impl ContractSummary {
+    pub fn from_metrics(metrics: &[&CoopMetrics]) -> Self {
+        let durations: Vec<f64> = metrics
+            .iter()
+            .map(|m| m.total_predicted_duration.duration_in_seconds as f64)
+            .collect();
+        let duration_of = |aggregate: Aggregate| {
+            aggregate
+                .reduce(&durations)
+                .map(|secs| Duration::new(secs.round() as i64))
+        };
+
+        Self {
+            coop_count: metrics.len(),
+            finished_count: metrics.iter().filter(|m| m.eggs_remaining <= 0.0).count(),
+            fastest_duration: duration_of(Aggregate::Min),
+            median_duration: duration_of(Aggregate::Median),
+        }
+    }
+}
+
+impl Display for ContractSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let format = |d: Option<Duration>| d.map(|d| d.format_too_long()).unwrap_or("-".into());
+        write!(
+            f,
+            "Coops: {} | Finished: {}\nFastest: {} | Median: {}",
+            self.coop_count,
+            self.finished_count,
+            format(self.fastest_duration),
+            format(self.median_duration)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(duration: i64, eggs_remaining: f64) -> CoopMetrics {
+        CoopMetrics {
+            total_predicted_duration: Duration::new(duration),
+            eggs_remaining,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn summarises_durations_and_finished_coops() {
+        let metrics = [
+            metrics(301, 0.0),
+            metrics(100, 1e9),
+            metrics(200, 0.0),
+            metrics(401, 5.0),
+        ];
+        let summary = ContractSummary::from_metrics(&metrics.iter().collect::<Vec<_>>());
+
+        // The median of 200 and 301 rounds to the nearest second.
+
+        assert_eq!(
+            summary,
+            ContractSummary {
+                coop_count: 4,
+                finished_count: 2,
+                fastest_duration: Some(Duration::new(100)),
+                median_duration: Some(Duration::new(251)),
+            }
+        );
+    }
+
+    #[test]
+    fn no_coops_have_no_durations() {
+        let summary = ContractSummary::from_metrics(&[]);
+
+        assert_eq!(summary, ContractSummary::default());
+        assert_eq!(
+            summary.to_string(),
+            "Coops: 0 | Finished: 0\nFastest: - | Median: -"
+        );
+    }
+}
@@ -1,19 +1,32 @@
 use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 
 use anyhow::{Error, Result};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use ei::ei::contract::{Goal, GradeSpec};
+use ei::ei::contract::{GradeSpec, PlayerGrade};
 use ei::ei::{contract_coop_status_response::ResponseStatus, Contract, ContractCoopStatusResponse};
 
 use crate::api::get_coop_status;
-use crate::error::InvalidCoopCode;
+use crate::clock::{Clock, SystemClock};
+use crate::error::{InvalidCoopCode, MissingGoals, MissingGradeSpec};
 use crate::formatter;
 use crate::formatter::discord_timestamp::DiscordTimestamp;
 use crate::formatter::duration::Duration;
 
+use super::boosts::BoostCatalog;
+use super::coop_metrics::CoopMetrics;
+use super::farm_simulation::{CoopSimulation, FarmState};
+use super::forecast::{self, CoopForecast, ForecastConfig, ForecastFarm};
+use super::goal_timeline::GoalPrediction;
+use super::modifiers::ContractModifiers;
+
+/// Predictions further out than this (10 years) are clamped, so a coop that isn't shipping at
+/// all still produces a number we can do arithmetic with.
+const MAX_PREDICTED_SECONDS: f64 = 10.0 * 365.0 * 86_400.0;
+
 #[derive(Debug, Error, Clone)]
 pub struct Coop {
     coop_status: ContractCoopStatusResponse,
@@ -22,22 +35,234 @@ pub struct Coop {
     // they belong in, which would be a pain in the ass to find after the fact.
     grade_spec: GradeSpec,
     contract_farm_max_secs_allowed: f64,
+    egg_goal: f64,
+    modifiers: ContractModifiers,
+
+    boost_catalog: Arc<BoostCatalog>,
+    metrics: CoopMetrics,
+    forecast: Option<CoopForecast>,
 }
 
 impl Coop {
-    fn new(coop_status: ContractCoopStatusResponse, contract: Contract) -> Self {
-        Self {
-            coop_status: coop_status.clone(),
-            grade_spec: contract
-                .grade_specs
-                .iter()
-                .find(|&g| g.grade() == coop_status.grade())
-                .unwrap_or(&GradeSpec::default())
-                .clone(),
-            contract_farm_max_secs_allowed: contract.length_seconds(),
+    /// Fails if the contract has no `GradeSpec` for the coop's grade, or the spec has no goals.
+    ///
+    /// # Details
+    ///
+    /// Every grade has its own goals, modifiers and length, so the coop's grade has to have a
+    /// matching `GradeSpec`. Only the length falls back to the contract's top-level value, for
+    /// specs that don't set one.
+    pub(crate) fn new(
+        coop_status: ContractCoopStatusResponse,
+        contract: Contract,
+        reference_time: i64,
+    ) -> Result<Self> {
+        let grade_spec = contract
+            .grade_specs
+            .iter()
+            .find(|&g| g.grade() == coop_status.grade())
+            .filter(|_| coop_status.grade() != PlayerGrade::GradeUnset)
+            .cloned()
+            .ok_or_else(|| MissingGradeSpec {
+                contract_id: contract.identifier().to_string(),
+                coop_id: coop_status.coop_identifier().to_string(),
+                grade: coop_status.grade(),
+            })?;
+        let egg_goal = grade_spec
+            .goals
+            .iter()
+            .map(|g| g.target_amount())
+            .max_by(|g1, g2| g1.partial_cmp(g2).unwrap_or(Ordering::Less))
+            .ok_or_else(|| MissingGoals {
+                contract_id: contract.identifier().to_string(),
+                grade: grade_spec.grade(),
+            })?;
+        let contract_farm_max_secs_allowed = grade_spec
+            .length_seconds
+            .unwrap_or_else(|| contract.length_seconds());
+
+        let mut coop = Self {
+            coop_status,
+            modifiers: ContractModifiers::new(&grade_spec.modifiers),
+            grade_spec,
+            contract_farm_max_secs_allowed,
+            egg_goal,
+            boost_catalog: Arc::default(),
+            metrics: CoopMetrics::default(),
+            forecast: None,
+        };
+        coop.refresh_metrics_at(reference_time);
+        Ok(coop)
+    }
+
+    pub fn metrics(&self) -> &CoopMetrics {
+        &self.metrics
+    }
+
+    /// The modifiers of the coop's grade. The farms' reported rates already include them, so
+    /// they're only applied to what the simulation works out itself, see
+    /// [`FarmState::from_contributor`].
+    pub fn modifiers(&self) -> &ContractModifiers {
+        &self.modifiers
+    }
+
+    /// How long the coop's grade gives it to finish.
+    pub fn contract_length(&self) -> Duration {
+        Duration::new(self.contract_farm_max_secs_allowed as i64)
+    }
+
+    /// Swaps the boost catalog used to predict IHR drops. Call [`Coop::refresh_metrics_at`]
+    /// afterwards for it to show in the metrics.
+    pub fn set_boost_catalog(&mut self, boost_catalog: Arc<BoostCatalog>) {
+        self.boost_catalog = boost_catalog;
+    }
+
+    /// Unix timestamp (in seconds) the server generated the status response at, if it says.
+    ///
+    /// # Details
+    ///
+    /// Everything in the response (seconds remaining, farm sync offsets, ...) is relative to this
+    /// instant, not to when we happen to look at it. The server's `last_sync` wins over
+    /// `local_timestamp`, which is set by whichever client asked and depends on its clock.
+    pub fn snapshot_time(&self) -> Option<i64> {
+        [
+            self.coop_status.last_sync(),
+            self.coop_status.local_timestamp(),
+        ]
+        .into_iter()
+        .find(|&t| t > 0.0)
+        .map(|t| t as i64)
+    }
+
+    /// Recomputes [`Coop::metrics`] as of `reference_time` (unix seconds).
+    ///
+    /// # Details
+    ///
+    /// Predictions are anchored to [`Coop::snapshot_time`], so rendering an old snapshot later
+    /// doesn't push its finishing time back. `reference_time` is only used for the data age, and
+    /// as the anchor for responses without a timestamp.
+    pub fn refresh_metrics_at(&mut self, reference_time: i64) {
+        self.metrics = self.compute_metrics(reference_time);
+    }
+
+    /// Plays every farm out once (see [`Coop::synced_farms`]) and derives everything else from
+    /// that.
+    fn compute_metrics(&self, reference_time: i64) -> CoopMetrics {
+        let synced_farms = self.synced_farms();
+        let egg_goal = self.egg_goal();
+        let shipped_eggs = self.shipped_eggs();
+        let offline_eggs: f64 = synced_farms.iter().map(|(_, eggs)| eggs).sum();
+        let eggs_remaining = (egg_goal - shipped_eggs - offline_eggs).max(0.0);
+        let simulation =
+            CoopSimulation::new(synced_farms.into_iter().map(|(farm, _)| farm).collect());
+        let predicted_seconds_remaining = simulation
+            .seconds_to_ship(eggs_remaining)
+            .min(MAX_PREDICTED_SECONDS) as i64;
+        let snapshot_time = self.snapshot_time().unwrap_or(reference_time);
+
+        CoopMetrics {
+            contract_id: self.contract_id().to_string(),
+            coop_id: self.coop_id().to_string(),
+            reference_time,
+            snapshot_time,
+            data_age: Duration::new((reference_time - snapshot_time).max(0)),
+            contract_length: self.contract_length(),
+            boosted_count: self.calc_boosted_count(),
+            total_tokens: self.calc_total_tokens(),
+            egg_goal,
+            shipped_eggs,
+            offline_eggs,
+            eggs_remaining,
+            shipping_rate: self.total_shipping_rate(),
+            predicted_seconds_remaining,
+            total_predicted_duration: self
+                .duration_from_secs_remaining(predicted_seconds_remaining),
+            finishing_time: self
+                .finishing_time_from_secs_remaining(snapshot_time, predicted_seconds_remaining),
+        }
+    }
+
+    /// The forecast from the last [`Coop::refresh_forecast`], if there was one.
+    pub fn forecast(&self) -> Option<&CoopForecast> {
+        self.forecast.as_ref()
+    }
+
+    /// Recomputes [`Coop::forecast`] from the current metrics. Sampling is expensive, so like the
+    /// metrics it's done once and read from then on.
+    pub fn refresh_forecast(&mut self, config: &ForecastConfig) {
+        self.forecast = Some(self.compute_forecast(config));
+    }
+
+    /// Monte Carlo forecast of when the coop finishes, see [`forecast::forecast_seconds_to_ship`].
+    fn compute_forecast(&self, config: &ForecastConfig) -> CoopForecast {
+        let farms: Vec<_> = self
+            .synced_farms()
+            .into_iter()
+            .zip(&self.coop_status.contributors)
+            .map(|((farm, _), p)| ForecastFarm {
+                farm,
+                boosted: p.boost_tokens_spent() >= 4,
+            })
+            .collect();
+
+        let secs_remaining = forecast::forecast_seconds_to_ship(
+            &farms,
+            self.metrics.eggs_remaining,
+            config,
+            forecast::coop_seed(self.coop_id()),
+        )
+        .map(|secs| secs.min(MAX_PREDICTED_SECONDS) as i64);
+
+        CoopForecast {
+            contract_length: self.contract_length(),
+            duration: secs_remaining.map(|secs| self.duration_from_secs_remaining(secs)),
+            finishing_time: secs_remaining.map(|secs| {
+                self.finishing_time_from_secs_remaining(self.metrics.snapshot_time, secs)
+            }),
         }
     }
 
+    /// Predicted finishing times for every goal of the coop's grade, in order.
+    pub fn goal_timeline(&self) -> Vec<GoalPrediction> {
+        let simulation = self.simulation();
+        let shipped = self.metrics.shipped_eggs + self.metrics.offline_eggs;
+
+        self.grade_spec
+            .goals
+            .iter()
+            .enumerate()
+            .map(|(i, goal)| {
+                let eggs_remaining = goal.target_amount() - shipped;
+                let finishing_time = (eggs_remaining > 0.0).then(|| {
+                    let secs = simulation
+                        .seconds_to_ship(eggs_remaining)
+                        .min(MAX_PREDICTED_SECONDS) as i64;
+                    self.finishing_time_from_secs_remaining(self.metrics.snapshot_time, secs)
+                });
+                GoalPrediction::new(i + 1, goal, finishing_time)
+            })
+            .collect()
+    }
+
+    fn duration_from_secs_remaining(&self, secs_remaining: i64) -> Duration {
+        Duration::new(
+            self.contract_farm_max_secs_allowed as i64
+                - self.coop_allowable_seconds_remaining() as i64
+                + secs_remaining
+                - self.coop_status.seconds_since_all_goals_achieved() as i64,
+        )
+    }
+
+    fn finishing_time_from_secs_remaining(
+        &self,
+        snapshot_time: i64,
+        secs_remaining: i64,
+    ) -> DiscordTimestamp {
+        DiscordTimestamp::new(
+            snapshot_time + secs_remaining
+                - self.coop_status.seconds_since_all_goals_achieved() as i64,
+        )
+    }
+
     pub fn coop_id(&self) -> &str {
         self.coop_status.coop_identifier()
     }
@@ -53,7 +278,56 @@ impl Coop {
         }
     }
 
+    pub fn grade(&self) -> PlayerGrade {
+        self.coop_status.grade()
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.coop_status.contributors.len()
+    }
+
     pub fn boosted_count(&self) -> u32 {
+        self.metrics.boosted_count
+    }
+
+    pub fn total_tokens(&self) -> u32 {
+        self.metrics.total_tokens
+    }
+
+    /// Eggs the whole coop ships **per hour**, i.e. what it lays, capped by each farm's shipping
+    /// capacity.
+    pub fn shipping_rate_per_hour(&self) -> f64 {
+        self.metrics.shipping_rate * 3600.0
+    }
+
+    /// Eggs laid by players while offline, which haven't been synced yet.
+    pub fn offline_eggs(&self) -> f64 {
+        self.metrics.offline_eggs
+    }
+
+    /// How much of the final goal is shipped, counting offline eggs, from 0 to 1.
+    pub fn progress(&self) -> f64 {
+        if self.metrics.egg_goal <= 0.0 {
+            return 1.0;
+        }
+        ((self.metrics.shipped_eggs + self.metrics.offline_eggs) / self.metrics.egg_goal)
+            .clamp(0.0, 1.0)
+    }
+
+    pub fn finishing_time(&self) -> DiscordTimestamp {
+        self.metrics.finishing_time
+    }
+
+    pub fn total_predicted_duration(&self) -> Duration {
+        self.metrics.total_predicted_duration
+    }
+
+    /// How old the status response was when the metrics were last refreshed.
+    pub fn data_age(&self) -> Duration {
+        self.metrics.data_age
+    }
+
+    fn calc_boosted_count(&self) -> u32 {
         self.coop_status
             .contributors
             .iter()
@@ -63,7 +337,7 @@ impl Coop {
             .expect("there's no way there can be more than 2^32 players in a coop")
     }
 
-    pub fn total_tokens(&self) -> u32 {
+    fn calc_total_tokens(&self) -> u32 {
         self.coop_status
             .contributors
             .iter()
@@ -71,22 +345,6 @@ impl Coop {
             .sum()
     }
 
-    pub fn finishing_time(&self) -> DiscordTimestamp {
-        DiscordTimestamp::new_from_secs_remaining(
-            self.predicted_seconds_remaining()
-                - self.coop_status.seconds_since_all_goals_achieved() as i64,
-        )
-    }
-
-    pub fn total_predicted_duration(&self) -> Duration {
-        let total = self.contract_farm_max_secs_allowed as i64
-            - self.coop_allowable_seconds_remaining() as i64
-            + self.predicted_seconds_remaining()
-            - self.coop_status.seconds_since_all_goals_achieved() as i64;
-
-        Duration::new(total)
-    }
-
     pub fn green_scrolled(&self) -> bool {
         self.coop_status.cleared_for_exit()
     }
@@ -95,17 +353,9 @@ impl Coop {
         self.coop_status.seconds_remaining()
     }
 
+    /// The target of the grade's final goal.
     fn egg_goal(&self) -> f64 {
-        self.grade_spec
-            .goals
-            .iter()
-            .max_by(|&g1, &g2| {
-                g1.target_amount()
-                    .partial_cmp(&g2.target_amount())
-                    .unwrap_or(Ordering::Less)
-            })
-            .unwrap_or(&Goal::default())
-            .target_amount()
+        self.egg_goal
     }
 
     fn shipped_eggs(&self) -> f64 {
@@ -121,32 +371,35 @@ impl Coop {
             .sum()
     }
 
-    /// Returns the total offline eggs laid by players.
+    /// Every contributor's farm as of the status response, paired with the eggs it shipped while
+    /// the player was offline.
     ///
     /// # Details
     ///
     /// `farm_info.timestamp()` is basically `LastFarmSyncTimeUnix - CurrentTimeUnix` in seconds,
     /// so the negative is required in the maths. Credits to WHALE for figuring that shit out.
     /// `farm_info` can also be `None` if the player is marked as `[departed]` or has a private farm.
-    fn total_offline_eggs(&self) -> f64 {
+    ///
+    /// Offline, the habs keep filling up to their capacity, with the IHR dropping as the boosts
+    /// that were running at the last sync run out; nobody is around to boost again.
+    fn synced_farms(&self) -> Vec<(FarmState, f64)> {
         self.coop_status
             .contributors
             .iter()
-            .map(|p| match &p.farm_info {
-                None => 0f64,
-                Some(farm) => p.contribution_rate() * -farm.timestamp(),
+            .map(|p| {
+                let mut farm = FarmState::from_contributor(p, &self.boost_catalog, &self.modifiers);
+                let offline_eggs = match &p.farm_info {
+                    None => 0f64,
+                    Some(info) => farm.advance_by(-info.timestamp()),
+                };
+                (farm, offline_eggs)
             })
-            .sum()
+            .collect()
     }
 
-    fn eggs_remaining(&self) -> f64 {
-        let zero = 0f64;
-        let calc_eggs = self.egg_goal() - self.shipped_eggs() - self.total_offline_eggs();
-        zero.max(calc_eggs)
-    }
-
-    fn predicted_seconds_remaining(&self) -> i64 {
-        (self.eggs_remaining() / self.total_shipping_rate()) as i64
+    /// Forward simulation of every contributor's farm, starting from the status response.
+    pub fn simulation(&self) -> CoopSimulation {
+        CoopSimulation::new(self.synced_farms().into_iter().map(|(f, _)| f).collect())
     }
 }
 
@@ -164,19 +417,7 @@ impl PartialOrd for Coop {
 
 impl Ord for Coop {
     fn cmp(&self, other: &Self) -> Ordering {
-        let mut ord = self
-            .total_predicted_duration()
-            .cmp(&other.total_predicted_duration());
-        if ord == Ordering::Equal {
-            ord = other.boosted_count().cmp(&self.boosted_count());
-        }
-        if ord == Ordering::Equal {
-            ord = other.total_tokens().cmp(&self.total_tokens());
-        }
-        if ord == Ordering::Equal {
-            ord = self.finishing_time().cmp(&other.finishing_time());
-        }
-        ord
+        self.metrics.leaderboard_cmp(&other.metrics)
     }
 }
 
@@ -232,10 +473,91 @@ impl<K> CoopBuilder<K, NoCoopCode> {
 
 impl CoopBuilder<WithContract, WithCoopCode> {
     pub async fn build(self) -> Result<Coop> {
+        self.build_with_clock(&SystemClock).await
+    }
+
+    /// Builds the coop with its metrics computed as of `clock`'s current time.
+    pub async fn build_with_clock(self, clock: &dyn Clock) -> Result<Coop> {
         let coop = get_coop_status(self.contract.0.identifier(), &self.coop_code.0).await?;
         match &coop.response_status() {
-            ResponseStatus::NoError => Ok(Coop::new(coop, self.contract.0)),
+            ResponseStatus::NoError => Coop::new(coop, self.contract.0, clock.unix_timestamp()),
             _ => Err(Error::from(InvalidCoopCode)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{contract, coop_status, shipping_coop_status};
+
+    use super::*;
+
+    #[test]
+    fn goals_are_predicted_in_order() {
+        let coop = Coop::new(
+            shipping_coop_status(1000.0, 10.0),
+            contract(&[500.0, 2000.0, 11_000.0]),
+            1_700_000_000,
+        )
+        .unwrap();
+
+        let finishing_times: Vec<_> = coop
+            .goal_timeline()
+            .iter()
+            .map(|g| (g.number, g.finishing_time.map(|t| t.unix_timestamp())))
+            .collect();
+        assert_eq!(
+            finishing_times,
+            [
+                (1, None),
+                (2, Some(1_700_000_100)),
+                (3, Some(1_700_001_000))
+            ]
+        );
+        assert_eq!(coop.metrics().eggs_remaining, 10_000.0);
+        assert_eq!(coop.metrics().predicted_seconds_remaining, 1000);
+    }
+
+    #[test]
+    fn egg_goal_is_the_final_goal() {
+        let coop = Coop::new(coop_status(), contract(&[1e9, 5e9, 3e9]), 0).unwrap();
+
+        assert_eq!(coop.metrics().egg_goal, 5e9);
+    }
+
+    #[test]
+    fn grade_without_goals_is_an_error() {
+        let err = Coop::new(coop_status(), contract(&[]), 0).unwrap_err();
+
+        assert!(err.downcast_ref::<MissingGoals>().is_some());
+    }
+
+    #[test]
+    fn snapshot_time_prefers_the_server_sync_time() {
+        let status = ContractCoopStatusResponse {
+            local_timestamp: Some(1_700_000_100.0),
+            last_sync: Some(1_700_000_000.0),
+            ..coop_status()
+        };
+        let coop = Coop::new(status, contract(&[1e9]), 0).unwrap();
+        assert_eq!(coop.snapshot_time(), Some(1_700_000_000));
+
+        let status = ContractCoopStatusResponse {
+            local_timestamp: Some(1_700_000_100.0),
+            ..coop_status()
+        };
+        let coop = Coop::new(status, contract(&[1e9]), 0).unwrap();
+        assert_eq!(coop.snapshot_time(), Some(1_700_000_100));
+    }
+
+    #[test]
+    fn missing_grade_is_an_error() {
+        let status = ContractCoopStatusResponse {
+            grade: Some(PlayerGrade::GradeA as i32),
+            ..coop_status()
+        };
+        let err = Coop::new(status, contract(&[1e9]), 0).unwrap_err();
+
+        assert!(err.downcast_ref::<MissingGradeSpec>().is_some());
+    }
+}
@@ -3,6 +3,8 @@ use std::fmt::Display;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::api::maj_api::MajCoopFlags;
+
 #[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq, Error)]
 pub enum CoopFlag {
     #[default]
@@ -19,52 +21,12 @@ impl Display for CoopFlag {
     }
 }
 
+/// Reads Maj's `coopFlags` object, see [`MajCoopFlags::coop_flag`].
 impl<'de> Deserialize<'de> for CoopFlag {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        struct CoopFlagVisitor;
-
-        impl<'de> serde::de::Visitor<'de> for CoopFlagVisitor {
-            type Value = CoopFlag;
-
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("coop flag variant")
-            }
-
-            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-            where
-                A: serde::de::MapAccess<'de>,
-            {
-                let mut any_grade = false;
-                let mut carry = false;
-                let mut fast_run = false;
-                let mut speed_run = false;
-
-                while let Some(key) = map.next_key::<&str>()? {
-                    match key {
-                        "anyGrade" => any_grade = map.next_value()?,
-                        "carry" => carry = map.next_value()?,
-                        "fastRun" => fast_run = map.next_value()?,
-                        "speedRun" => speed_run = map.next_value()?,
-                        _ => {}
-                    }
-                }
-
-                if speed_run {
-                    Ok(CoopFlag::Speedrun)
-                } else if fast_run {
-                    Ok(CoopFlag::Fastrun)
-                } else if carry {
-                    Ok(CoopFlag::Carry)
-                } else if any_grade {
-                    Ok(CoopFlag::AnyGrade)
-                } else {
-                    Ok(CoopFlag::NoFlags)
-                }
-            }
-        }
-        deserializer.deserialize_map(CoopFlagVisitor)
+        MajCoopFlags::deserialize(deserializer).map(|flags| flags.coop_flag())
     }
 }
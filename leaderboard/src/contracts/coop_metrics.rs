@@ -0,0 +1,51 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::formatter::discord_timestamp::DiscordTimestamp;
+use crate::formatter::duration::Duration;
+
+/// Everything the leaderboard needs to know about a coop, computed once at `reference_time`.
+///
+/// # Details
+///
+/// Working these out walks every contributor, so a `Coop` computes them once and sorting, tables
+/// and JSON all read from the same snapshot. Coops that are compared against each other should
+/// share a `reference_time`, see [`super::coop::Coop::refresh_metrics_at`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CoopMetrics {
+    pub contract_id: String,
+    pub coop_id: String,
+    /// Unix timestamp (in seconds) the metrics were computed at.
+    pub reference_time: i64,
+    /// Unix timestamp (in seconds) the predictions are anchored to, see
+    /// [`super::coop::Coop::snapshot_time`].
+    pub snapshot_time: i64,
+    /// How old the status response was at `reference_time`.
+    pub data_age: Duration,
+    /// The length of the coop's grade.
+    pub contract_length: Duration,
+    pub boosted_count: u32,
+    pub total_tokens: u32,
+    pub egg_goal: f64,
+    pub shipped_eggs: f64,
+    pub offline_eggs: f64,
+    pub eggs_remaining: f64,
+    /// **Per second** shipping rate of the entire coop.
+    pub shipping_rate: f64,
+    pub predicted_seconds_remaining: i64,
+    pub total_predicted_duration: Duration,
+    pub finishing_time: DiscordTimestamp,
+}
+
+impl CoopMetrics {
+    /// Leaderboard order: shortest predicted duration first, then most boosted players, then most
+    /// tokens, then earliest finish.
+    pub fn leaderboard_cmp(&self, other: &Self) -> Ordering {
+        self.total_predicted_duration
+            .cmp(&other.total_predicted_duration)
+            .then_with(|| other.boosted_count.cmp(&self.boosted_count))
+            .then_with(|| other.total_tokens.cmp(&self.total_tokens))
+            .then_with(|| self.finishing_time.cmp(&other.finishing_time))
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+
+use anyhow::{Context, Error, Result};
+use log::warn;
+use sqlx::postgres::PgPool;
+
+use crate::api::maj_api::{self, MajCoops, MAJ_COOPS_API_URL};
+
+use super::coop_flag::CoopFlag;
+
+pub type CoopCodesFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<String>>> + Send + 'a>>;
+pub type CoopCodesBatchFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<HashMap<String, Vec<String>>>> + Send + 'a>>;
+
+/// Somewhere we can look up which coops are running for a contract.
+///
+/// The trait returns a boxed future rather than using `async fn` so that sources can be stored as
+/// `dyn CoopSource` and mixed together in a [`CompositeCoopSource`].
+pub trait CoopSource: Send + Sync {
+    fn coop_codes<'a>(&'a self, contract_id: &'a str, coop_flag: CoopFlag) -> CoopCodesFuture<'a>;
+
+    /// Looks up several contracts at once, keyed by contract ID.
+    ///
+    /// Defaults to one [`CoopSource::coop_codes`] call per contract; sources that can do better
+    /// (e.g. a single HTTP request) should override it.
+    fn coop_codes_batch<'a>(
+        &'a self,
+        contract_ids: &'a [String],
+        coop_flag: CoopFlag,
+    ) -> CoopCodesBatchFuture<'a> {
+        Box::pin(async move {
+            let mut all_codes = HashMap::new();
+            for id in contract_ids {
+                all_codes.insert(id.clone(), self.coop_codes(id, coop_flag).await?);
+            }
+            Ok(all_codes)
+        })
+    }
+}
+
+/// Coop codes as listed by Maj's Wonky API.
+#[derive(Debug, Clone)]
+pub struct MajCoopSource {
+    api_url: String,
+}
+
+impl MajCoopSource {
+    pub fn new() -> Self {
+        Self::with_url(MAJ_COOPS_API_URL)
+    }
+
+    pub fn with_url(api_url: impl Into<String>) -> Self {
+        Self {
+            api_url: api_url.into(),
+        }
+    }
+}
+
+impl Default for MajCoopSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CoopSource for MajCoopSource {
+    fn coop_codes<'a>(&'a self, contract_id: &'a str, coop_flag: CoopFlag) -> CoopCodesFuture<'a> {
+        Box::pin(maj_api::get_maj_active_coop_codes_from(
+            &self.api_url,
+            contract_id,
+            coop_flag,
+        ))
+    }
+
+    fn coop_codes_batch<'a>(
+        &'a self,
+        contract_ids: &'a [String],
+        coop_flag: CoopFlag,
+    ) -> CoopCodesBatchFuture<'a> {
+        Box::pin(maj_api::get_maj_active_coop_codes_batch_from(
+            &self.api_url,
+            contract_ids,
+            coop_flag,
+        ))
+    }
+}
+
+/// A fixed list of coop codes, usually read from a JSON file.
+///
+/// # Details
+///
+/// The file maps contract IDs to the same coop objects Maj returns, e.g.
+///
+/// ```json
+/// { "some-contract": [ { "code": "abc123", "coopFlags": { "speedRun": true } } ] }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StaticCoopSource {
+    coops: HashMap<String, Vec<MajCoops>>,
+}
+
+impl StaticCoopSource {
+    pub fn new(coops: HashMap<String, Vec<MajCoops>>) -> Self {
+        Self { coops }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).context(format!(
+            "Cannot read coop codes file \"{}\"",
+            path.display()
+        ))?;
+        let coops = serde_json::from_str(&json).context(format!(
+            "Cannot parse coop codes file \"{}\"",
+            path.display()
+        ))?;
+        Ok(Self::new(coops))
+    }
+}
+
+impl CoopSource for StaticCoopSource {
+    fn coop_codes<'a>(&'a self, contract_id: &'a str, coop_flag: CoopFlag) -> CoopCodesFuture<'a> {
+        let codes = self
+            .coops
+            .get(contract_id)
+            .map(|coops| {
+                coops
+                    .iter()
+                    .filter(|&c| c.coop_flags.coop_flag() == coop_flag)
+                    .map(|c| c.code.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Box::pin(async { Ok(codes) })
+    }
+}
+
+/// Coop codes registered by hand in the `coop_registry` table.
+#[derive(Debug, Clone)]
+pub struct DatabaseCoopSource {
+    pool: PgPool,
+}
+
+impl DatabaseCoopSource {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let pool = PgPool::connect(database_url)
+            .await
+            .context("Cannot connect to the coop registry database")?;
+        Ok(Self::new(pool))
+    }
+
+    pub async fn create_table(&self) -> Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS coop_registry (
+                contract_id TEXT NOT NULL,
+                coop_code TEXT NOT NULL,
+                coop_flag TEXT NOT NULL,
+                PRIMARY KEY (contract_id, coop_code)
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn register(
+        &self,
+        contract_id: &str,
+        coop_code: &str,
+        coop_flag: CoopFlag,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO coop_registry (contract_id, coop_code, coop_flag) VALUES ($1, $2, $3)
+            ON CONFLICT (contract_id, coop_code) DO UPDATE SET coop_flag = EXCLUDED.coop_flag",
+        )
+        .bind(contract_id)
+        .bind(coop_code)
+        .bind(coop_flag.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+impl CoopSource for DatabaseCoopSource {
+    fn coop_codes<'a>(&'a self, contract_id: &'a str, coop_flag: CoopFlag) -> CoopCodesFuture<'a> {
+        Box::pin(async move {
+            let codes = sqlx::query_scalar::<_, String>(
+                "SELECT coop_code FROM coop_registry
+                WHERE contract_id = $1 AND coop_flag = $2
+                ORDER BY coop_code",
+            )
+            .bind(contract_id)
+            .bind(coop_flag.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(codes)
+        })
+    }
+}
+
+/// Merges the coop codes of several sources, in the order the sources were added.
+///
+/// # Details
+///
+/// A source that errors is logged and skipped, so one source being down doesn't take the others
+/// with it. Only when every source fails is the last error returned.
+#[derive(Default)]
+pub struct CompositeCoopSource {
+    sources: Vec<Box<dyn CoopSource>>,
+}
+
+impl CompositeCoopSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_source(mut self, source: impl CoopSource + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    pub fn add_source(&mut self, source: impl CoopSource + 'static) {
+        self.sources.push(Box::new(source));
+    }
+}
+
+impl CoopSource for CompositeCoopSource {
+    fn coop_codes<'a>(&'a self, contract_id: &'a str, coop_flag: CoopFlag) -> CoopCodesFuture<'a> {
+        Box::pin(async move {
+            let mut merged: Vec<String> = vec![];
+            let mut last_err: Option<Error> = None;
+            let mut any_ok = false;
+
+            for source in &self.sources {
+                match source.coop_codes(contract_id, coop_flag).await {
+                    Ok(codes) => {
+                        any_ok = true;
+                        for code in codes {
+                            if !merged.contains(&code) {
+                                merged.push(code);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Coop source failed for \"{}\": {:#}", contract_id, e);
+                        last_err = Some(e);
+                    }
+                }
+            }
+
+            match last_err {
+                Some(e) if !any_ok => Err(e),
+                _ => Ok(merged),
+            }
+        })
+    }
+
+    fn coop_codes_batch<'a>(
+        &'a self,
+        contract_ids: &'a [String],
+        coop_flag: CoopFlag,
+    ) -> CoopCodesBatchFuture<'a> {
+        Box::pin(async move {
+            let mut merged: HashMap<String, Vec<String>> = HashMap::new();
+            let mut last_err: Option<Error> = None;
+            let mut any_ok = false;
+
+            for source in &self.sources {
+                match source.coop_codes_batch(contract_ids, coop_flag).await {
+                    Ok(all_codes) => {
+                        any_ok = true;
+                        for (id, codes) in all_codes {
+                            let merged_codes = merged.entry(id).or_default();
+                            for code in codes {
+                                if !merged_codes.contains(&code) {
+                                    merged_codes.push(code);
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Coop source failed for {:?}: {:#}", contract_ids, e);
+                        last_err = Some(e);
+                    }
+                }
+            }
+
+            match last_err {
+                Some(e) if !any_ok => Err(e),
+                _ => Ok(merged),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    struct FailingCoopSource;
+
+    impl CoopSource for FailingCoopSource {
+        fn coop_codes<'a>(&'a self, _: &'a str, _: CoopFlag) -> CoopCodesFuture<'a> {
+            Box::pin(async { Err(anyhow!("down")) })
+        }
+    }
+
+    fn static_source(coops: &[(&str, &str, CoopFlag)]) -> StaticCoopSource {
+        let mut by_contract: HashMap<String, Vec<MajCoops>> = HashMap::new();
+        for &(contract_id, code, coop_flags) in coops {
+            by_contract
+                .entry(contract_id.to_string())
+                .or_default()
+                .push(MajCoops::new(code, coop_flags.into()));
+        }
+        StaticCoopSource::new(by_contract)
+    }
+
+    #[tokio::test]
+    async fn composite_merges_sources_without_duplicates() {
+        let source = CompositeCoopSource::new()
+            .with_source(static_source(&[
+                ("c", "abc", CoopFlag::Speedrun),
+                ("c", "def", CoopFlag::Speedrun),
+            ]))
+            .with_source(static_source(&[
+                ("c", "def", CoopFlag::Speedrun),
+                ("c", "ghi", CoopFlag::Speedrun),
+                ("c", "jkl", CoopFlag::Carry),
+            ]));
+
+        let codes = source.coop_codes("c", CoopFlag::Speedrun).await.unwrap();
+        assert_eq!(codes, ["abc", "def", "ghi"]);
+    }
+
+    #[tokio::test]
+    async fn composite_skips_failing_sources() {
+        let source = CompositeCoopSource::new()
+            .with_source(FailingCoopSource)
+            .with_source(static_source(&[("c", "abc", CoopFlag::Speedrun)]));
+
+        let codes = source.coop_codes("c", CoopFlag::Speedrun).await.unwrap();
+        assert_eq!(codes, ["abc"]);
+
+        let ids = ["c".to_string()];
+        let all_codes = source
+            .coop_codes_batch(&ids, CoopFlag::Speedrun)
+            .await
+            .unwrap();
+        assert_eq!(all_codes["c"], ["abc"]);
+    }
+
+    #[tokio::test]
+    async fn composite_fails_only_when_every_source_fails() {
+        let source = CompositeCoopSource::new()
+            .with_source(FailingCoopSource)
+            .with_source(FailingCoopSource);
+        assert!(source.coop_codes("c", CoopFlag::Speedrun).await.is_err());
+
+        let ids = ["c".to_string()];
+        assert!(source
+            .coop_codes_batch(&ids, CoopFlag::Speedrun)
+            .await
+            .is_err());
+
+        // No sources at all isn't a failure, there's just nothing to find.
+        let codes = CompositeCoopSource::new()
+            .coop_codes("c", CoopFlag::Speedrun)
+            .await
+            .unwrap();
+        assert!(codes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn default_batch_looks_up_every_contract() {
+        let source = static_source(&[
+            ("a", "abc", CoopFlag::Speedrun),
+            ("b", "def", CoopFlag::Speedrun),
+        ]);
+
+        let ids = ["a".to_string(), "b".to_string(), "c".to_string()];
+        let all_codes = source
+            .coop_codes_batch(&ids, CoopFlag::Speedrun)
+            .await
+            .unwrap();
+        assert_eq!(all_codes.len(), 3);
+        assert_eq!(all_codes["a"], ["abc"]);
+        assert_eq!(all_codes["b"], ["def"]);
+        assert!(all_codes["c"].is_empty());
+    }
+
+    #[tokio::test]
+    async fn static_source_reads_maj_coops_from_a_file() {
+        let path = std::env::temp_dir().join(format!("coop-codes-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{
+                "c": [
+                    { "code": "abc", "coopFlags": { "speedRun": true } },
+                    { "code": "def", "coopFlags": { "carry": true } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let source = StaticCoopSource::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let source = source.unwrap();
+        assert_eq!(
+            source.coop_codes("c", CoopFlag::Speedrun).await.unwrap(),
+            ["abc"]
+        );
+        assert_eq!(
+            source.coop_codes("c", CoopFlag::Carry).await.unwrap(),
+            ["def"]
+        );
+        assert!(StaticCoopSource::from_file(&path).is_err());
+    }
+}
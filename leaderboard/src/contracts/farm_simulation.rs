@@ -0,0 +1,395 @@
+use ei::ei::contract_coop_status_response::ContributionInfo;
+use ei::ei::game_modifier::GameDimension;
+use ei::ei::FarmProductionParams;
+
+use super::boosts::BoostCatalog;
+use super::modifiers::ContractModifiers;
+
+/// How close to a cap counts as "at the cap", to stop floating point error from producing
+/// endless zero-length segments.
+const CAP_EPSILON: f64 = 1e-9;
+
+/// The parts of a player's farm that decide how fast it ships eggs.
+///
+/// # Details
+///
+/// Values are as reported in `FarmProductionParams`, i.e. all per second:
+/// `elr` is eggs per chicken, `ihr` is chickens hatched and `sr` is eggs shipped at most.
+/// `growth_starts_in` holds off hatching for that many seconds, e.g. for a player who hasn't
+/// boosted yet. `ihr_changes` lists upcoming `(seconds from now, new IHR)` changes, soonest first,
+/// e.g. from boosts running out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FarmState {
+    pub population: f64,
+    pub capacity: f64,
+    pub elr: f64,
+    pub ihr: f64,
+    pub sr: f64,
+    pub growth_starts_in: f64,
+    pub ihr_changes: Vec<(f64, f64)>,
+}
+
+impl FarmState {
+    pub fn from_production_params(params: &FarmProductionParams) -> Self {
+        Self {
+            population: params.farm_population(),
+            capacity: params.farm_capacity(),
+            elr: params.elr(),
+            ihr: params.ihr(),
+            sr: params.sr(),
+            growth_starts_in: 0.0,
+            ihr_changes: vec![],
+        }
+    }
+
+    /// A farm that ships at `rate` forever, for players we have no production params for.
+    pub fn constant(rate: f64) -> Self {
+        Self {
+            population: 1.0,
+            capacity: 1.0,
+            elr: rate,
+            ihr: 0.0,
+            sr: rate,
+            growth_starts_in: 0.0,
+            ihr_changes: vec![],
+        }
+    }
+
+    /// Uses the production params when there are any, otherwise the reported contribution rate.
+    ///
+    /// # Details
+    ///
+    /// If the farm info lists its habs, their population and capacity win over the production
+    /// params' totals. Its running boosts are looked up in `boosts` to work out how the IHR drops
+    /// as they expire.
+    ///
+    /// The production params and the contribution rate are what the farm actually did, so they
+    /// already include the grade's `modifiers`, as does the IHR schedule that scales the reported
+    /// IHR. The hab list is the farm's own, so its capacity gets the hab capacity modifier.
+    pub fn from_contributor(
+        player: &ContributionInfo,
+        boosts: &BoostCatalog,
+        modifiers: &ContractModifiers,
+    ) -> Self {
+        let mut farm = match &player.production_params {
+            Some(params) if params.elr() > 0.0 => Self::from_production_params(params),
+            _ => return Self::constant(player.contribution_rate()),
+        };
+
+        if let Some(info) = &player.farm_info {
+            let hab_capacity: u64 = info.hab_capacity.iter().sum();
+            if hab_capacity > 0 {
+                farm.capacity =
+                    hab_capacity as f64 * modifiers.multiplier(GameDimension::HabCapacity);
+                farm.population = info.hab_population.iter().sum::<u64>() as f64;
+            }
+            farm.ihr_changes = boosts.hatchery_schedule(farm.ihr, &info.active_boosts);
+        }
+        farm
+    }
+
+    /// Moves the farm `secs` into the future and returns the eggs it shipped on the way.
+    pub fn advance_by(&mut self, secs: f64) -> f64 {
+        let mut shipped = 0.0;
+        let mut left = secs;
+        while left > 0.0 {
+            let segment = self.next_change_in().min(left);
+            shipped += self.shipping_rate() * segment
+                + self.shipping_rate_slope() * segment * segment / 2.0;
+            self.advance(segment);
+            left -= segment;
+        }
+        shipped
+    }
+
+    /// Eggs shipped per second right now: `min(ELR × population, shipping)`.
+    pub fn shipping_rate(&self) -> f64 {
+        (self.elr * self.population).min(self.sr)
+    }
+
+    fn is_growing(&self) -> bool {
+        self.growth_starts_in <= 0.0
+            && self.ihr > 0.0
+            && self.population < self.capacity * (1.0 - CAP_EPSILON)
+            && self.elr * self.population < self.sr * (1.0 - CAP_EPSILON)
+    }
+
+    /// How fast [`FarmState::shipping_rate`] is currently increasing, per second.
+    fn shipping_rate_slope(&self) -> f64 {
+        if self.is_growing() {
+            self.elr * self.ihr
+        } else {
+            0.0
+        }
+    }
+
+    /// Seconds until the shipping rate changes how it grows, i.e. hatching starts, the IHR
+    /// changes, the habs are full or the farm is laying more than it can ship.
+    fn next_change_in(&self) -> f64 {
+        let to_ihr_change = self
+            .ihr_changes
+            .first()
+            .map_or(f64::INFINITY, |&(secs, _)| secs);
+        if self.growth_starts_in > 0.0 {
+            return self.growth_starts_in.min(to_ihr_change);
+        }
+        if !self.is_growing() {
+            return to_ihr_change;
+        }
+        let to_capacity = (self.capacity - self.population) / self.ihr;
+        let to_shipping_cap = (self.sr / self.elr - self.population) / self.ihr;
+        to_capacity.min(to_shipping_cap).min(to_ihr_change).max(0.0)
+    }
+
+    /// Moves the farm `secs` into the future. Only valid for `secs <= next_change_in()`.
+    fn advance(&mut self, secs: f64) {
+        if self.growth_starts_in > 0.0 {
+            self.growth_starts_in = (self.growth_starts_in - secs).max(0.0);
+        } else if self.is_growing() {
+            self.population = (self.population + self.ihr * secs).min(self.capacity);
+        }
+
+        self.ihr_changes
+            .iter_mut()
+            .for_each(|(in_secs, _)| *in_secs -= secs);
+        while let Some(&(in_secs, ihr)) = self.ihr_changes.first() {
+            if in_secs > 0.0 {
+                break;
+            }
+            self.ihr = ihr;
+            self.ihr_changes.remove(0);
+        }
+    }
+}
+
+/// Forward simulation of every farm in a coop.
+///
+/// # Details
+///
+/// Each farm's shipping rate is piecewise linear (growing while the habs fill, flat once they're
+/// full or shipping is capped), so the coop's total rate is too. The simulation jumps from one
+/// change to the next and solves each segment exactly instead of stepping through time.
+#[derive(Debug, Clone, Default)]
+pub struct CoopSimulation {
+    farms: Vec<FarmState>,
+}
+
+impl CoopSimulation {
+    pub fn new(farms: Vec<FarmState>) -> Self {
+        Self { farms }
+    }
+
+    pub fn from_contributors<'a>(
+        players: impl IntoIterator<Item = &'a ContributionInfo>,
+        boosts: &BoostCatalog,
+        modifiers: &ContractModifiers,
+    ) -> Self {
+        Self::new(
+            players
+                .into_iter()
+                .map(|p| FarmState::from_contributor(p, boosts, modifiers))
+                .collect(),
+        )
+    }
+
+    pub fn farms(&self) -> &[FarmState] {
+        &self.farms
+    }
+
+    pub fn shipping_rate(&self) -> f64 {
+        self.farms.iter().map(|f| f.shipping_rate()).sum()
+    }
+
+    /// Seconds until the coop has shipped `eggs` more eggs, or `f64::INFINITY` if it never will.
+    pub fn seconds_to_ship(&self, eggs: f64) -> f64 {
+        let mut sim = self.clone();
+        let mut elapsed = 0.0;
+        let mut remaining = eggs;
+
+        while remaining > 0.0 {
+            let rate = sim.shipping_rate();
+            let slope: f64 = sim.farms.iter().map(|f| f.shipping_rate_slope()).sum();
+            let segment = sim.next_change_in();
+
+            if let Some(secs) = secs_to_ship_in_segment(remaining, rate, slope, segment) {
+                return elapsed + secs;
+            }
+            if segment.is_infinite() {
+                return f64::INFINITY;
+            }
+
+            remaining -= rate * segment + slope * segment * segment / 2.0;
+            elapsed += segment;
+            sim.advance(segment);
+        }
+
+        elapsed
+    }
+
+    fn next_change_in(&self) -> f64 {
+        self.farms
+            .iter()
+            .map(|f| f.next_change_in())
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn advance(&mut self, secs: f64) {
+        self.farms.iter_mut().for_each(|f| f.advance(secs));
+    }
+}
+
+/// Solves `rate * t + slope * t² / 2 = eggs` for `0 <= t <= segment`.
+fn secs_to_ship_in_segment(eggs: f64, rate: f64, slope: f64, segment: f64) -> Option<f64> {
+    let secs = if slope > 0.0 {
+        (-rate + (rate * rate + 2.0 * slope * eggs).sqrt()) / slope
+    } else if rate > 0.0 {
+        eggs / rate
+    } else {
+        return None;
+    };
+    (secs <= segment).then_some(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use ei::ei::{GameModifier, PlayerFarmInfo};
+
+    use super::*;
+
+    fn growing_farm(capacity: f64, sr: f64) -> FarmState {
+        FarmState {
+            population: 0.0,
+            capacity,
+            elr: 1.0,
+            ihr: 1.0,
+            sr,
+            ..Default::default()
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn growth_continues_at_base_rate_after_boosts_expire() {
+        let mut farm = FarmState {
+            ihr: 10.0,
+            ihr_changes: vec![(10.0, 1.0)],
+            ..growing_farm(1000.0, 1000.0)
+        };
+
+        farm.advance_by(20.0);
+        assert_close(farm.population, 110.0);
+        assert_eq!(farm.ihr, 1.0);
+        assert!(farm.ihr_changes.is_empty());
+    }
+
+    #[test]
+    fn growth_waits_for_the_first_boost() {
+        let mut farm = FarmState {
+            growth_starts_in: 5.0,
+            ..growing_farm(1000.0, 1000.0)
+        };
+
+        farm.advance_by(10.0);
+        assert_close(farm.population, 5.0);
+    }
+
+    #[test]
+    fn shipping_stops_growing_at_hab_capacity() {
+        let mut farm = growing_farm(10.0, 100.0);
+
+        // 10 seconds growing to 10 eggs/s, then 10 more at that rate.
+        assert_close(farm.advance_by(20.0), 50.0 + 100.0);
+        assert_close(farm.population, 10.0);
+    }
+
+    #[test]
+    fn shipping_stops_growing_at_shipping_capacity() {
+        let mut farm = growing_farm(1000.0, 5.0);
+
+        assert_close(farm.advance_by(20.0), 12.5 + 75.0);
+        assert_close(farm.shipping_rate(), 5.0);
+    }
+
+    #[test]
+    fn seconds_to_ship_matches_advance_by() {
+        let sim = CoopSimulation::new(vec![
+            FarmState::constant(5.0),
+            FarmState {
+                ihr: 2.0,
+                ihr_changes: vec![(3.0, 0.5)],
+                ..growing_farm(20.0, 100.0)
+            },
+        ]);
+
+        let secs = sim.seconds_to_ship(500.0);
+        let shipped: f64 = sim.farms().iter().map(|f| f.clone().advance_by(secs)).sum();
+        assert_close(shipped, 500.0);
+    }
+
+    #[test]
+    fn seconds_to_ship_is_infinite_without_shipping() {
+        let sim = CoopSimulation::new(vec![FarmState::constant(0.0)]);
+
+        assert_eq!(sim.seconds_to_ship(1.0), f64::INFINITY);
+        assert_eq!(sim.seconds_to_ship(0.0), 0.0);
+    }
+
+    #[test]
+    fn modifiers_only_apply_to_derived_capacity() {
+        let modifiers = ContractModifiers::new(&[
+            GameModifier {
+                dimension: Some(GameDimension::HabCapacity as i32),
+                value: Some(1.5),
+                ..Default::default()
+            },
+            GameModifier {
+                dimension: Some(GameDimension::EggLayingRate as i32),
+                value: Some(2.0),
+                ..Default::default()
+            },
+        ]);
+        let player = ContributionInfo {
+            contribution_rate: Some(7.0),
+            production_params: Some(FarmProductionParams {
+                farm_population: Some(50.0),
+                farm_capacity: Some(100.0),
+                elr: Some(2.0),
+                ihr: Some(1.0),
+                sr: Some(90.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let boosts = BoostCatalog::builtin();
+
+        // Reported capacity and rates are used as is.
+        let farm = FarmState::from_contributor(&player, &boosts, &modifiers);
+        assert_eq!((farm.capacity, farm.elr, farm.sr), (100.0, 2.0, 90.0));
+
+        let with_habs = ContributionInfo {
+            farm_info: Some(PlayerFarmInfo {
+                hab_capacity: vec![100, 200],
+                hab_population: vec![20, 30],
+                ..Default::default()
+            }),
+            ..player.clone()
+        };
+        let farm = FarmState::from_contributor(&with_habs, &boosts, &modifiers);
+        assert_eq!((farm.capacity, farm.elr), (450.0, 2.0));
+
+        let without_params = ContributionInfo {
+            production_params: None,
+            ..player
+        };
+        let farm = FarmState::from_contributor(&without_params, &boosts, &modifiers);
+        assert_eq!(farm, FarmState::constant(7.0));
+    }
+}
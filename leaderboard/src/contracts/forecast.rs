@@ -0,0 +1,288 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::error::InvalidForecastConfig;
+use crate::formatter::discord_timestamp::DiscordTimestamp;
+use crate::formatter::duration::Duration;
+
+use super::farm_simulation::{CoopSimulation, FarmState};
+
+/// How much the Monte Carlo forecast is allowed to vary each player per sample.
+///
+/// # Details
+///
+/// Every value is checked when the config is built or deserialized, so sampling never sees a
+/// range it can't draw from.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "RawForecastConfig")]
+pub struct ForecastConfig {
+    samples: usize,
+    max_offline_fraction: f64,
+    max_boost_delay_secs: f64,
+    rate_drift: f64,
+    seed: u64,
+}
+
+/// [`ForecastConfig`] as written, before it's checked.
+#[derive(Deserialize)]
+struct RawForecastConfig {
+    samples: usize,
+    max_offline_fraction: f64,
+    max_boost_delay_secs: f64,
+    rate_drift: f64,
+    seed: u64,
+}
+
+impl TryFrom<RawForecastConfig> for ForecastConfig {
+    type Error = InvalidForecastConfig;
+
+    fn try_from(raw: RawForecastConfig) -> Result<Self, Self::Error> {
+        Self::new(
+            raw.samples,
+            raw.max_offline_fraction,
+            raw.max_boost_delay_secs,
+            raw.rate_drift,
+            raw.seed,
+        )
+    }
+}
+
+impl ForecastConfig {
+    /// Fails unless there's at least one sample, `max_offline_fraction` and `rate_drift` are
+    /// within `0..=1` and `max_boost_delay_secs` is a finite, non-negative number.
+    pub fn new(
+        samples: usize,
+        max_offline_fraction: f64,
+        max_boost_delay_secs: f64,
+        rate_drift: f64,
+        seed: u64,
+    ) -> Result<Self, InvalidForecastConfig> {
+        let invalid = |field, expected| Err(InvalidForecastConfig { field, expected });
+        if samples == 0 {
+            return invalid("samples", "at least 1");
+        }
+        if !(0.0..=1.0).contains(&max_offline_fraction) {
+            return invalid("max_offline_fraction", "between 0 and 1");
+        }
+        if !(max_boost_delay_secs.is_finite() && max_boost_delay_secs >= 0.0) {
+            return invalid("max_boost_delay_secs", "a non-negative number");
+        }
+        if !(0.0..=1.0).contains(&rate_drift) {
+            return invalid("rate_drift", "between 0 and 1");
+        }
+
+        Ok(Self {
+            samples,
+            max_offline_fraction,
+            max_boost_delay_secs,
+            rate_drift,
+            seed,
+        })
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Upper bound on the share of the remaining time a player spends offline, during which
+    /// their farm doesn't count.
+    pub fn max_offline_fraction(&self) -> f64 {
+        self.max_offline_fraction
+    }
+
+    /// Upper bound on how long a player who hasn't boosted yet waits before they start hatching.
+    pub fn max_boost_delay_secs(&self) -> f64 {
+        self.max_boost_delay_secs
+    }
+
+    /// ELR is scaled by a factor drawn from `1 ± rate_drift`.
+    pub fn rate_drift(&self) -> f64 {
+        self.rate_drift
+    }
+
+    /// Combined with the coop ID, so the same coop always gets the same forecast.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        Self {
+            samples: 500,
+            max_offline_fraction: 0.2,
+            max_boost_delay_secs: 30.0 * 60.0,
+            rate_drift: 0.1,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct Percentiles<T> {
+    pub p10: T,
+    pub p50: T,
+    pub p90: T,
+}
+
+impl<T> Percentiles<T> {
+    pub fn map<U>(self, f: impl Fn(T) -> U) -> Percentiles<U> {
+        Percentiles {
+            p10: f(self.p10),
+            p50: f(self.p50),
+            p90: f(self.p90),
+        }
+    }
+}
+
+/// A coop's forecast, as total durations and as finishing times.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct CoopForecast {
+    /// The length of the coop's grade, past which a duration is "Too long".
+    pub contract_length: Duration,
+    pub duration: Percentiles<Duration>,
+    pub finishing_time: Percentiles<DiscordTimestamp>,
+}
+
+impl CoopForecast {
+    /// The P10 to P90 duration, e.g. "3h10m–3h45m".
+    pub fn duration_range(&self) -> String {
+        format!(
+            "{}\u{2013}{}",
+            self.duration.p10.format_within(self.contract_length),
+            self.duration.p90.format_within(self.contract_length)
+        )
+    }
+}
+
+/// One player's farm as far as the forecast is concerned.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ForecastFarm {
+    pub farm: FarmState,
+    pub boosted: bool,
+}
+
+/// The per-coop part of the forecast seed: the FNV-1a hash of the coop ID.
+///
+/// # Details
+///
+/// Unlike `DefaultHasher`, whose algorithm can change between Rust releases, FNV-1a is fixed, so
+/// a coop gets the same forecast no matter which build computes it.
+pub fn coop_seed(coop_id: &str) -> u64 {
+    coop_id.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Samples `config.samples` variations of `farms` and returns the P10/P50/P90 of the seconds it
+/// takes them to ship `eggs`.
+pub fn forecast_seconds_to_ship(
+    farms: &[ForecastFarm],
+    eggs: f64,
+    config: &ForecastConfig,
+    seed: u64,
+) -> Percentiles<f64> {
+    let mut rng = StdRng::seed_from_u64(config.seed ^ seed);
+    let mut samples: Vec<f64> = (0..config.samples)
+        .map(|_| {
+            let sampled = farms
+                .iter()
+                .map(|f| sample_farm(f, config, &mut rng))
+                .collect();
+            CoopSimulation::new(sampled).seconds_to_ship(eggs)
+        })
+        .collect();
+    samples.sort_by(|a, b| a.total_cmp(b));
+
+    let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p).round() as usize];
+    Percentiles {
+        p10: percentile(0.1),
+        p50: percentile(0.5),
+        p90: percentile(0.9),
+    }
+}
+
+fn sample_farm(farm: &ForecastFarm, config: &ForecastConfig, rng: &mut StdRng) -> FarmState {
+    let mut sampled = farm.farm.clone();
+    let drift = rng.gen_range(1.0 - config.rate_drift..=1.0 + config.rate_drift);
+    let online = 1.0 - rng.gen_range(0.0..=config.max_offline_fraction);
+
+    sampled.elr *= drift * online;
+    sampled.sr *= online;
+    if !farm.boosted {
+        sampled.growth_starts_in = rng.gen_range(0.0..=config.max_boost_delay_secs);
+    }
+    sampled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn farms() -> Vec<ForecastFarm> {
+        vec![
+            ForecastFarm {
+                farm: FarmState::constant(100.0),
+                boosted: true,
+            },
+            ForecastFarm {
+                farm: FarmState::constant(50.0),
+                boosted: false,
+            },
+        ]
+    }
+
+    #[test]
+    fn config_rejects_out_of_range_values() {
+        assert!(ForecastConfig::new(0, 0.2, 0.0, 0.1, 0).is_err());
+        assert!(ForecastConfig::new(10, 1.5, 0.0, 0.1, 0).is_err());
+        assert!(ForecastConfig::new(10, -0.1, 0.0, 0.1, 0).is_err());
+        assert!(ForecastConfig::new(10, 0.2, -1.0, 0.1, 0).is_err());
+        assert!(ForecastConfig::new(10, 0.2, f64::INFINITY, 0.1, 0).is_err());
+        assert!(ForecastConfig::new(10, 0.2, 0.0, 2.0, 0).is_err());
+        assert!(ForecastConfig::new(10, 0.2, 0.0, f64::NAN, 0).is_err());
+        assert!(ForecastConfig::new(10, 1.0, 0.0, 1.0, 0).is_ok());
+    }
+
+    #[test]
+    fn config_is_checked_when_deserialized() {
+        let json = r#"{"samples":10,"max_offline_fraction":0.2,"max_boost_delay_secs":0,
+            "rate_drift":3,"seed":0}"#;
+        assert!(serde_json::from_str::<ForecastConfig>(json).is_err());
+
+        let config = ForecastConfig::default();
+        let round_trip: ForecastConfig =
+            serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(round_trip, config);
+    }
+
+    #[test]
+    fn percentiles_are_ordered_and_reproducible() {
+        let config = ForecastConfig::default();
+        let forecast = forecast_seconds_to_ship(&farms(), 1e6, &config, 42);
+
+        assert!(forecast.p10 <= forecast.p50 && forecast.p50 <= forecast.p90);
+        assert_eq!(
+            forecast,
+            forecast_seconds_to_ship(&farms(), 1e6, &config, 42)
+        );
+    }
+
+    #[test]
+    fn coop_seed_is_pinned() {
+        assert_eq!(coop_seed(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(coop_seed("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(coop_seed("foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn no_variation_matches_the_simulation() {
+        let config = ForecastConfig::new(20, 0.0, 0.0, 0.0, 0).unwrap();
+        let forecast = forecast_seconds_to_ship(&farms(), 1.5e6, &config, 7);
+
+        // 150 eggs a second between the two farms.
+        assert_eq!(forecast.p10, 1e4);
+        assert_eq!(forecast.p90, 1e4);
+    }
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use ei::ei::contract::Goal;
+use ei::ei::RewardType;
+
+use crate::formatter::discord_timestamp::DiscordTimestamp;
+
+/// When a coop is predicted to reach one of its grade's goals, and what it gets for it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GoalPrediction {
+    /// 1-based, as numbered in-game.
+    pub number: usize,
+    pub target_amount: f64,
+    pub reward_type: RewardType,
+    pub reward_sub_type: String,
+    pub reward_amount: f64,
+    /// `None` if the coop has already reached the goal.
+    pub finishing_time: Option<DiscordTimestamp>,
+}
+
+impl GoalPrediction {
+    pub fn new(number: usize, goal: &Goal, finishing_time: Option<DiscordTimestamp>) -> Self {
+        Self {
+            number,
+            target_amount: goal.target_amount(),
+            reward_type: goal.reward_type(),
+            reward_sub_type: goal.reward_sub_type().to_string(),
+            reward_amount: goal.reward_amount(),
+            finishing_time,
+        }
+    }
+
+    pub fn is_reached(&self) -> bool {
+        self.finishing_time.is_none()
+    }
+
+    /// The reward as shown to players, e.g. "3 boost token" or "1 boost (tachyon_prism_orange)".
+    pub fn reward(&self) -> String {
+        let reward_type = self
+            .reward_type
+            .as_str_name()
+            .to_lowercase()
+            .replace('_', " ");
+        if self.reward_sub_type.is_empty() {
+            format!("{} {}", self.reward_amount, reward_type)
+        } else {
+            format!(
+                "{} {} ({})",
+                self.reward_amount, reward_type, self.reward_sub_type
+            )
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt::{Display, Formatter};
+
+use ei::ei::game_modifier::GameDimension;
+use ei::ei::GameModifier;
+
+/// The `GameModifier`s of a contract grade.
+///
+/// # Details
+///
+/// Every modifier's value is a multiplier on its dimension, and modifiers on the same dimension
+/// stack multiplicatively. The game applies them before it reports a farm's production params,
+/// so the simulation only applies them to values it derives itself, like hab capacity.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContractModifiers {
+    modifiers: Vec<GameModifier>,
+}
+
+impl ContractModifiers {
+    pub fn new(modifiers: &[GameModifier]) -> Self {
+        Self {
+            modifiers: modifiers.to_vec(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modifiers.is_empty()
+    }
+
+    pub fn modifiers(&self) -> &[GameModifier] {
+        &self.modifiers
+    }
+
+    /// The combined multiplier on `dimension`, `1.0` if nothing modifies it.
+    pub fn multiplier(&self, dimension: GameDimension) -> f64 {
+        self.modifiers
+            .iter()
+            .filter(|m| m.dimension() == dimension)
+            .map(|m| m.value())
+            .product()
+    }
+}
+
+impl Display for ContractModifiers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let modifiers = self
+            .modifiers
+            .iter()
+            .map(|m| format!("{} \u{d7}{}", dimension_name(m.dimension()), m.value()))
+            .collect::<Vec<_>>();
+        write!(f, "{}", modifiers.join(", "))
+    }
+}
+
+fn dimension_name(dimension: GameDimension) -> &'static str {
+    match dimension {
+        GameDimension::Earnings => "Earnings",
+        GameDimension::AwayEarnings => "Away earnings",
+        GameDimension::InternalHatcheryRate => "Hatchery rate",
+        GameDimension::EggLayingRate => "Egg laying rate",
+        GameDimension::ShippingCapacity => "Shipping capacity",
+        GameDimension::HabCapacity => "Hab capacity",
+        GameDimension::VehicleCost => "Vehicle cost",
+        GameDimension::HabCost => "Hab cost",
+        GameDimension::ResearchCost => "Research cost",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modifier(dimension: GameDimension, value: f64) -> GameModifier {
+        GameModifier {
+            dimension: Some(dimension as i32),
+            value: Some(value),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn multipliers_stack() {
+        let modifiers = ContractModifiers::new(&[
+            modifier(GameDimension::EggLayingRate, 2.0),
+            modifier(GameDimension::EggLayingRate, 1.5),
+            modifier(GameDimension::HabCapacity, 0.5),
+        ]);
+
+        assert_eq!(modifiers.multiplier(GameDimension::EggLayingRate), 3.0);
+        assert_eq!(modifiers.multiplier(GameDimension::HabCapacity), 0.5);
+        assert_eq!(modifiers.multiplier(GameDimension::ShippingCapacity), 1.0);
+    }
+
+    #[test]
+    fn display_lists_every_modifier() {
+        let modifiers = ContractModifiers::new(&[
+            modifier(GameDimension::ShippingCapacity, 0.5),
+            modifier(GameDimension::Earnings, 3.0),
+        ]);
+
+        assert_eq!(
+            modifiers.to_string(),
+            "Shipping capacity \u{d7}0.5, Earnings \u{d7}3"
+        );
+        assert_eq!(ContractModifiers::default().to_string(), "");
+    }
+}
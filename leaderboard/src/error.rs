@@ -2,6 +2,8 @@ use std::fmt::{Display, Formatter};
 
 use thiserror::Error;
 
+use ei::ei::contract::PlayerGrade;
+
 #[derive(Debug, Copy, Clone, Error)]
 pub struct InvalidCoopCode;
 
@@ -37,3 +39,114 @@ impl Display for EmptyContractsResponse {
         write!(f, "Empty ContractsResponse")
     }
 }
+
+#[derive(Debug, Clone, Error)]
+pub struct MissingGradeSpec {
+    pub contract_id: String,
+    pub coop_id: String,
+    pub grade: PlayerGrade,
+}
+
+impl Display for MissingGradeSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.grade {
+            PlayerGrade::GradeUnset => write!(f, "Coop \"{}\" has no grade set", self.coop_id),
+            grade => write!(
+                f,
+                "Contract \"{}\" has no spec for {} (coop \"{}\")",
+                self.contract_id,
+                grade.as_str_name(),
+                self.coop_id
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+pub struct MissingGoals {
+    pub contract_id: String,
+    pub grade: PlayerGrade,
+}
+
+impl Display for MissingGoals {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Contract \"{}\" has no goals for {}",
+            self.contract_id,
+            self.grade.as_str_name()
+        )
+    }
+}
+
+/// A [`crate::contracts::forecast::ForecastConfig`] value out of range, by field name.
+#[derive(Debug, Clone, Error)]
+pub struct InvalidForecastConfig {
+    pub field: &'static str,
+    pub expected: &'static str,
+}
+
+impl Display for InvalidForecastConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid forecast config: `{}` must be {}",
+            self.field, self.expected
+        )
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+pub struct InvalidDuration(pub String);
+
+impl Display for InvalidDuration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid duration \"{}\", expected something like \"1d2h30m\"",
+            self.0
+        )
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+pub struct InvalidDiscordTimestamp(pub String);
+
+impl Display for InvalidDiscordTimestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid Discord timestamp \"{}\"", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Error)]
+pub struct PageOverflow {
+    pub budget: usize,
+    pub needed: usize,
+}
+
+impl Display for PageOverflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "A page needs at least {} characters, over the budget of {}",
+            self.needed, self.budget
+        )
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+pub struct UnknownPlaceholder {
+    pub name: String,
+    pub known: Vec<String>,
+}
+
+impl Display for UnknownPlaceholder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unknown placeholder \"{{{}}}\", expected one of {}",
+            self.name,
+            self.known.join(", ")
+        )
+    }
+}
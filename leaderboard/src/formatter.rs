@@ -2,3 +2,5 @@ pub mod discord_table;
 pub mod discord_timestamp;
 pub mod duration;
 pub mod string_formatter;
+pub mod table_renderer;
+pub mod template;
@@ -1,16 +1,29 @@
-use crate::formatter::string_formatter::{self, StringAlignment};
+use crate::error::PageOverflow;
+use crate::formatter::string_formatter::{self, Overflow, StringAlignment};
+use crate::formatter::table_renderer::{
+    self, ColumnLayout, DiscordRenderer, TableCells, TableRenderer,
+};
+use serde::{Deserialize, Serialize};
 use std::slice::Iter;
 
-pub struct DiscordTable<T: Clone> {
+/// A table of `T`s, one row each, with columns that format a row into a cell.
+///
+/// # Details
+///
+/// Columns borrow their row, so formatting a cell never copies it. Column functions are
+/// `Send + Sync`, so a table of `Send + Sync` rows can be built and rendered on any thread.
+pub struct DiscordTable<T> {
     columns: Vec<DiscordTableColumn<T>>,
     data_rows: Vec<T>,
+    aggregates: Vec<Aggregate>,
 }
 
-impl<T: Clone> DiscordTable<T> {
+impl<T> DiscordTable<T> {
     pub fn new() -> DiscordTable<T> {
         DiscordTable {
             columns: vec![],
             data_rows: vec![],
+            aggregates: vec![],
         }
     }
 
@@ -18,8 +31,18 @@ impl<T: Clone> DiscordTable<T> {
         self.columns.push(col);
     }
 
-    pub fn add_data_rows(&mut self, rows: &[T]) {
-        rows.iter().for_each(|r| self.data_rows.push(r.clone()));
+    pub fn insert_column(&mut self, index: usize, col: DiscordTableColumn<T>) {
+        self.columns.insert(index, col);
+    }
+
+    pub fn extend_data_rows(&mut self, rows: impl IntoIterator<Item = T>) {
+        self.data_rows.extend(rows);
+    }
+
+    /// Adds a row under the data with `aggregate` of every column that has a reducer for it, see
+    /// [`TableCells::aggregate_rows`].
+    pub fn add_aggregate_row(&mut self, aggregate: Aggregate) {
+        self.aggregates.push(aggregate);
     }
 
     pub fn columns(&self) -> Iter<'_, DiscordTableColumn<T>> {
@@ -30,57 +53,342 @@ impl<T: Clone> DiscordTable<T> {
         self.data_rows.iter()
     }
 
-    pub fn get_table_header(&self) -> String {
-        let col_names = self
+    /// The aggregate rows, labelled in the first column that doesn't show a value for them.
+    /// There are none without data, and rows no column has a reducer for are left out.
+    fn aggregate_rows(&self) -> Vec<Vec<String>> {
+        if self.data_rows.is_empty() {
+            return vec![];
+        }
+
+        self.aggregates
+            .iter()
+            .filter_map(|&aggregate| {
+                let mut cells: Vec<Option<String>> = self
+                    .columns
+                    .iter()
+                    .map(|c| c.reduce(&self.data_rows, aggregate))
+                    .collect();
+                if cells.iter().all(Option::is_none) {
+                    return None;
+                }
+                if let Some(label) = cells.iter_mut().find(|c| c.is_none()) {
+                    *label = Some(aggregate.label().to_string());
+                }
+                Some(cells.into_iter().map(Option::unwrap_or_default).collect())
+            })
+            .collect()
+    }
+
+    /// Formats every cell, for a [`crate::formatter::table_renderer::TableRenderer`]. Auto-width
+    /// columns are sized to their widest value or name, as shown on Discord, counting aggregate
+    /// rows. Columns [`DiscordTableColumn::with_number`] fill in [`TableCells::numbers`].
+    pub fn cells(&self) -> TableCells {
+        let rows: Vec<Vec<String>> = self
+            .data_rows
+            .iter()
+            .map(|row| self.columns.iter().map(|c| (c.column_fn)(row)).collect())
+            .collect();
+        let numbers = self
+            .data_rows
+            .iter()
+            .map(|row| {
+                self.columns
+                    .iter()
+                    .map(|c| c.number_fn.as_ref().map(|number_fn| number_fn(row)))
+                    .collect()
+            })
+            .collect();
+        let aggregate_rows = self.aggregate_rows();
+
+        let columns = self
             .columns
             .iter()
-            .map(|c| string_formatter::align(c.name.clone(), c.width, c.alignment))
-            .collect::<Vec<_>>();
+            .enumerate()
+            .map(|(i, c)| {
+                let width = match c.width {
+                    ColumnWidth::Fixed(width) => width,
+                    ColumnWidth::Auto { min, max } => {
+                        let widest = rows
+                            .iter()
+                            .chain(&aggregate_rows)
+                            .map(|row| string_formatter::visible_width(&row[i]))
+                            .fold(string_formatter::display_width(&c.name), usize::max);
+                        widest.clamp(min, max.unwrap_or(usize::MAX).max(min))
+                    }
+                };
+                ColumnLayout {
+                    name: c.name.clone(),
+                    width,
+                    alignment: c.alignment,
+                    overflow: c.overflow,
+                }
+            })
+            .collect();
 
-        format!("`{}`", col_names.join("|"))
+        TableCells {
+            columns,
+            rows,
+            numbers,
+            aggregate_rows,
+        }
     }
 
-    pub fn get_table_body(&self) -> String {
-        let mut body = String::new();
-        for row in self.data_rows.as_slice() {
-            let cols_in_a_row = self
-                .columns
-                .iter()
-                .map(|c| string_formatter::align((c.column_fn)(row.clone()), c.width, c.alignment))
-                .collect::<Vec<_>>();
-
-            body += &format!("{}\n", cols_in_a_row.join("|"));
-        }
+    /// The table split into pages of at most `budget`, see [`table_renderer::paginate`].
+    pub fn pages(
+        &self,
+        renderer: &dyn TableRenderer,
+        budget: usize,
+    ) -> Result<Vec<String>, PageOverflow> {
+        table_renderer::paginate(renderer, &self.cells(), "", "", budget)
+    }
 
-        body
+    pub fn get_table_header(&self) -> String {
+        DiscordRenderer.render_header(&self.cells())
+    }
+
+    pub fn get_table_body(&self) -> String {
+        DiscordRenderer.render_body(&self.cells())
     }
 }
 
-impl<T: Clone> Default for DiscordTable<T> {
+impl<T> Default for DiscordTable<T> {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// How wide a column is.
+///
+/// # Details
+///
+/// In a config, a fixed width is just a number (`width = 10`) and an automatic one is a table
+/// with optional bounds (`width = {}` or `width = { min = 4, max = 20 }`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColumnWidth {
+    Fixed(usize),
+    /// As wide as the widest value or the column name, kept within `min..=max`.
+    Auto {
+        #[serde(default)]
+        min: usize,
+        #[serde(default)]
+        max: Option<usize>,
+    },
+}
+
+/// A summary of a column over every row.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregate {
+    Min,
+    Median,
+    Mean,
+    Max,
+    Total,
+}
+
+impl Aggregate {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Min => "Min",
+            Self::Median => "Median",
+            Self::Mean => "Mean",
+            Self::Max => "Max",
+            Self::Total => "Total",
+        }
+    }
+
+    /// `None` if there are no values. The median of an even number of values is the mean of the
+    /// middle two.
+    pub fn reduce(&self, values: &[f64]) -> Option<f64> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let count = values.len() as f64;
+        match self {
+            Self::Min => values.iter().copied().reduce(f64::min),
+            Self::Max => values.iter().copied().reduce(f64::max),
+            Self::Total => Some(values.iter().sum()),
+            Self::Mean => Some(values.iter().sum::<f64>() / count),
+            Self::Median => {
+                let mut sorted = values.to_vec();
+                sorted.sort_by(f64::total_cmp);
+                let mid = sorted.len() / 2;
+                match sorted.len() % 2 {
+                    0 => Some((sorted[mid - 1] + sorted[mid]) / 2.0),
+                    _ => Some(sorted[mid]),
+                }
+            }
+        }
+    }
+}
+
+/// Turns a row into the number behind one of its cells.
+type NumberFn<T> = Box<dyn Fn(&T) -> f64 + Send + Sync>;
+
+/// Turns a column into numbers for [`Aggregate`]s and formats the results.
+struct ColumnReducer<T> {
+    value_fn: NumberFn<T>,
+    format_fn: Box<dyn Fn(f64) -> String + Send + Sync>,
+    aggregates: Vec<Aggregate>,
+}
+
 pub struct DiscordTableColumn<T> {
     name: String,
-    column_fn: Box<dyn Fn(T) -> String>,
-    width: usize,
+    column_fn: Box<dyn Fn(&T) -> String + Send + Sync>,
+    number_fn: Option<NumberFn<T>>,
+    width: ColumnWidth,
     alignment: StringAlignment,
+    overflow: Overflow,
+    reducer: Option<ColumnReducer<T>>,
 }
 
 impl<T> DiscordTableColumn<T> {
     pub fn new(
         name: impl Into<String>,
-        column_fn: impl Fn(T) -> String + Clone + 'static,
+        column_fn: impl Fn(&T) -> String + Send + Sync + 'static,
         width: usize,
         alignment: StringAlignment,
     ) -> DiscordTableColumn<T> {
         DiscordTableColumn {
             name: name.into(),
-            column_fn: Box::new(column_fn.clone()),
-            width,
+            column_fn: Box::new(column_fn),
+            number_fn: None,
+            width: ColumnWidth::Fixed(width),
             alignment,
+            overflow: Overflow::default(),
+            reducer: None,
         }
     }
+
+    /// A column sized from its data, see [`ColumnWidth::Auto`].
+    pub fn auto(
+        name: impl Into<String>,
+        column_fn: impl Fn(&T) -> String + Send + Sync + 'static,
+        alignment: StringAlignment,
+    ) -> DiscordTableColumn<T> {
+        Self::new(name, column_fn, 0, alignment).with_width(ColumnWidth::Auto { min: 0, max: None })
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_alignment(mut self, alignment: StringAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn with_width(mut self, width: ColumnWidth) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// The number behind each cell, e.g. seconds for a duration, for formats with types.
+    pub fn with_number(mut self, number_fn: impl Fn(&T) -> f64 + Send + Sync + 'static) -> Self {
+        self.number_fn = Some(Box::new(number_fn));
+        self
+    }
+
+    /// Lets the column fill the `aggregates` rows of its table: `value_fn` turns a row into a
+    /// number and `format_fn` shows the aggregated number.
+    pub fn with_reducer(
+        mut self,
+        value_fn: impl Fn(&T) -> f64 + Send + Sync + 'static,
+        format_fn: impl Fn(f64) -> String + Send + Sync + 'static,
+        aggregates: &[Aggregate],
+    ) -> Self {
+        self.reducer = Some(ColumnReducer {
+            value_fn: Box::new(value_fn),
+            format_fn: Box::new(format_fn),
+            aggregates: aggregates.to_vec(),
+        });
+        self
+    }
+
+    /// `aggregate` of the column over `rows`, if the column has a reducer for it.
+    fn reduce(&self, rows: &[T], aggregate: Aggregate) -> Option<String> {
+        let reducer = self
+            .reducer
+            .as_ref()
+            .filter(|r| r.aggregates.contains(&aggregate))?;
+        let values: Vec<f64> = rows.iter().map(|row| (reducer.value_fn)(row)).collect();
+        aggregate.reduce(&values).map(|v| (reducer.format_fn)(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduce_without_values_is_none() {
+        for aggregate in [
+            Aggregate::Min,
+            Aggregate::Median,
+            Aggregate::Mean,
+            Aggregate::Max,
+            Aggregate::Total,
+        ] {
+            assert_eq!(aggregate.reduce(&[]), None);
+        }
+    }
+
+    #[test]
+    fn reduce_summarises_values() {
+        let values = [4.0, 1.0, 3.0];
+
+        assert_eq!(Aggregate::Min.reduce(&values), Some(1.0));
+        assert_eq!(Aggregate::Max.reduce(&values), Some(4.0));
+        assert_eq!(Aggregate::Total.reduce(&values), Some(8.0));
+        assert_eq!(Aggregate::Mean.reduce(&values), Some(8.0 / 3.0));
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(Aggregate::Median.reduce(&[5.0, 1.0, 3.0]), Some(3.0));
+        assert_eq!(Aggregate::Median.reduce(&[5.0, 1.0, 4.0, 2.0]), Some(3.0));
+        assert_eq!(Aggregate::Median.reduce(&[7.0]), Some(7.0));
+    }
+
+    fn table(rows: &[u32]) -> DiscordTable<u32> {
+        let mut table = DiscordTable::new();
+        table.add_column(DiscordTableColumn::new(
+            "Name",
+            |n: &u32| format!("#{}", n),
+            6,
+            StringAlignment::Left,
+        ));
+        table.add_column(
+            DiscordTableColumn::new("N", |n: &u32| n.to_string(), 3, StringAlignment::Left)
+                .with_number(|&n| n as f64)
+                .with_reducer(|&n| n as f64, |v| format!("{:.0}", v), &[Aggregate::Total]),
+        );
+        table.add_aggregate_row(Aggregate::Total);
+        table.add_aggregate_row(Aggregate::Median);
+        table.extend_data_rows(rows.iter().copied());
+        table
+    }
+
+    #[test]
+    fn aggregate_rows_are_kept_apart_from_the_data() {
+        let cells = table(&[2, 5]).cells();
+
+        assert_eq!(cells.rows, [["#2", "2"], ["#5", "5"]]);
+        assert_eq!(cells.numbers, [[None, Some(2.0)], [None, Some(5.0)]]);
+        // No column reduces to a median, so there's no row for it.
+        assert_eq!(cells.aggregate_rows, [["Total", "7"]]);
+    }
+
+    #[test]
+    fn no_aggregate_rows_without_data() {
+        assert!(table(&[]).cells().aggregate_rows.is_empty());
+    }
 }
@@ -1,44 +1,278 @@
 use std::cmp::Ordering;
 use std::fmt::Display;
+use std::ops::{Add, AddAssign, Range, Sub, SubAssign};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use thiserror::Error;
+use time::{OffsetDateTime, UtcOffset};
 
-#[derive(Debug, Error, Clone, Serialize, Deserialize, Default)]
+use crate::clock::{Clock, SystemClock};
+use crate::error::InvalidDiscordTimestamp;
+
+use super::duration::Duration;
+
+/// Units for relative timestamps, largest first, in seconds. Months and years are approximate,
+/// like Discord's.
+const RELATIVE_UNITS: &[(u64, &str)] = &[
+    (365 * 86_400, "year"),
+    (30 * 86_400, "month"),
+    (86_400, "day"),
+    (3_600, "hour"),
+    (60, "minute"),
+    (1, "second"),
+];
+
+/// A unix timestamp (in seconds), serialized as the bare number.
+///
+/// # Details
+///
+/// It used to be serialized as `{"time": N}`, which is still accepted when deserializing.
+#[derive(Debug, Error, Clone, Copy, Serialize, Default)]
+#[serde(transparent)]
 pub struct DiscordTimestamp {
     time: i64,
 }
 
 impl DiscordTimestamp {
     pub fn display(&self, display_mode: DiscordTimestampDisplay) -> String {
-        let identifier = match display_mode {
-            DiscordTimestampDisplay::ShortDate => "d",
-            DiscordTimestampDisplay::FullDate => "D",
-            DiscordTimestampDisplay::HourMinuteTime => "t",
-            DiscordTimestampDisplay::HourMinuteSecondTime => "T",
-            DiscordTimestampDisplay::FullDateTime => "f",
-            DiscordTimestampDisplay::FullDateTimeDayOfWeek => "F",
-            DiscordTimestampDisplay::Relative => "R",
+        format!("<t:{}:{}>", self.time, display_mode.identifier())
+    }
+
+    /// Parses a single `<t:N>` or `<t:N:X>` token, the inverse of [`DiscordTimestamp::display`].
+    /// Without a style Discord shows [`DiscordTimestampDisplay::FullDateTime`].
+    pub fn parse_markup(
+        token: &str,
+    ) -> Result<(Self, DiscordTimestampDisplay), InvalidDiscordTimestamp> {
+        let invalid = || InvalidDiscordTimestamp(token.to_string());
+        let inner = token
+            .trim()
+            .strip_prefix("<t:")
+            .and_then(|t| t.strip_suffix('>'))
+            .ok_or_else(invalid)?;
+
+        let (time, display_mode) = match inner.split_once(':') {
+            Some((time, identifier)) => (
+                time,
+                DiscordTimestampDisplay::from_identifier(identifier).ok_or_else(invalid)?,
+            ),
+            None => (inner, DiscordTimestampDisplay::FullDateTime),
         };
+        let time = time.parse().map_err(|_| invalid())?;
 
-        format!("<t:{}:{}>", self.time, identifier)
+        Ok((Self::new(time), display_mode))
+    }
+
+    /// Every valid timestamp token in `text`, with the byte range it covers.
+    pub fn find_markup(text: &str) -> Vec<(Range<usize>, Self, DiscordTimestampDisplay)> {
+        let mut found = vec![];
+        let mut search_from = 0;
+        while let Some(start) = text[search_from..].find("<t:").map(|i| i + search_from) {
+            let Some(end) = text[start..].find('>').map(|i| i + start + 1) else {
+                break;
+            };
+            match Self::parse_markup(&text[start..end]) {
+                Ok((timestamp, display_mode)) => {
+                    found.push((start..end, timestamp, display_mode));
+                    search_from = end;
+                }
+                Err(_) => search_from = start + 1,
+            }
+        }
+        found
+    }
+
+    /// The text Discord would show for `display_mode`, in `offset`'s timezone and relative to
+    /// `clock`'s current time.
+    ///
+    /// # Details
+    ///
+    /// Follows Discord's en-US formatting, e.g. "10/19/2026", "October 19, 2026 4:20 PM" or
+    /// "in 3 hours". Timestamps `OffsetDateTime` can't represent fall back to the raw markup.
+    pub fn display_plain(
+        &self,
+        display_mode: DiscordTimestampDisplay,
+        offset: UtcOffset,
+        clock: &dyn Clock,
+    ) -> String {
+        if display_mode == DiscordTimestampDisplay::Relative {
+            return self.display_relative(clock);
+        }
+        let Ok(date_time) = self.to_offset_date_time() else {
+            return self.display(display_mode);
+        };
+        let date_time = date_time.to_offset(offset);
+
+        let short_date = format!(
+            "{}/{}/{}",
+            u8::from(date_time.month()),
+            date_time.day(),
+            date_time.year()
+        );
+        let full_date = format!(
+            "{} {}, {}",
+            date_time.month(),
+            date_time.day(),
+            date_time.year()
+        );
+        let (hour, am_pm) = match date_time.hour() {
+            0 => (12, "AM"),
+            h @ 1..=11 => (h, "AM"),
+            12 => (12, "PM"),
+            h => (h - 12, "PM"),
+        };
+        let time = format!("{}:{:02} {}", hour, date_time.minute(), am_pm);
+
+        match display_mode {
+            DiscordTimestampDisplay::ShortDate => short_date,
+            DiscordTimestampDisplay::FullDate => full_date,
+            DiscordTimestampDisplay::HourMinuteTime => time,
+            DiscordTimestampDisplay::HourMinuteSecondTime => format!(
+                "{}:{:02}:{:02} {}",
+                hour,
+                date_time.minute(),
+                date_time.second(),
+                am_pm
+            ),
+            DiscordTimestampDisplay::FullDateTime => format!("{full_date} {time}"),
+            DiscordTimestampDisplay::FullDateTimeDayOfWeek => {
+                format!("{}, {full_date} {time}", date_time.weekday())
+            }
+            DiscordTimestampDisplay::Relative => unreachable!("handled above"),
+        }
+    }
+
+    fn display_relative(&self, clock: &dyn Clock) -> String {
+        let secs = self.time.saturating_sub(clock.unix_timestamp());
+        let abs = secs.unsigned_abs();
+        let (value, unit) = RELATIVE_UNITS
+            .iter()
+            .find(|&&(unit_secs, _)| abs >= unit_secs)
+            .map(|&(unit_secs, unit)| (abs / unit_secs, unit))
+            .unwrap_or((abs, "second"));
+        let plural = if value == 1 { "" } else { "s" };
+
+        if secs >= 0 {
+            format!("in {value} {unit}{plural}")
+        } else {
+            format!("{value} {unit}{plural} ago")
+        }
     }
 
     pub fn new_from_secs_remaining(secs_remaining: i64) -> Self {
-        let current_time = chrono::Utc::now().timestamp();
+        Self::new_from_secs_remaining_with(secs_remaining, &SystemClock)
+    }
+
+    pub fn new_from_secs_remaining_with(secs_remaining: i64, clock: &dyn Clock) -> Self {
         Self {
-            time: secs_remaining + current_time,
+            time: secs_remaining.saturating_add(clock.unix_timestamp()),
         }
     }
 
     pub fn new_from_now() -> Self {
-        let now = chrono::Utc::now().timestamp();
-        Self { time: now }
+        Self::new_from_now_with(&SystemClock)
+    }
+
+    pub fn new_from_now_with(clock: &dyn Clock) -> Self {
+        Self {
+            time: clock.unix_timestamp(),
+        }
     }
 
     pub fn new(time: i64) -> Self {
         Self { time }
     }
+
+    pub fn unix_timestamp(&self) -> i64 {
+        self.time
+    }
+
+    /// Fails if the timestamp is outside of what `OffsetDateTime` can represent.
+    pub fn to_offset_date_time(&self) -> Result<OffsetDateTime, time::error::ComponentRange> {
+        OffsetDateTime::from_unix_timestamp(self.time)
+    }
+}
+
+impl From<OffsetDateTime> for DiscordTimestamp {
+    fn from(date_time: OffsetDateTime) -> Self {
+        Self::new(date_time.unix_timestamp())
+    }
+}
+
+impl TryFrom<DiscordTimestamp> for OffsetDateTime {
+    type Error = time::error::ComponentRange;
+
+    fn try_from(timestamp: DiscordTimestamp) -> Result<Self, Self::Error> {
+        timestamp.to_offset_date_time()
+    }
+}
+
+impl Add<Duration> for DiscordTimestamp {
+    type Output = DiscordTimestamp;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        Self::new(self.time.saturating_add(rhs.duration_in_seconds))
+    }
+}
+
+impl AddAssign<Duration> for DiscordTimestamp {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Duration> for DiscordTimestamp {
+    type Output = DiscordTimestamp;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        Self::new(self.time.saturating_sub(rhs.duration_in_seconds))
+    }
+}
+
+impl SubAssign<Duration> for DiscordTimestamp {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sub for DiscordTimestamp {
+    type Output = Duration;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Duration::new(self.time.saturating_sub(rhs.time))
+    }
+}
+
+impl<'de> Deserialize<'de> for DiscordTimestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawDiscordTimestamp {
+            Seconds(i64),
+            Legacy { time: i64 },
+        }
+
+        let time = match RawDiscordTimestamp::deserialize(deserializer)? {
+            RawDiscordTimestamp::Seconds(time) | RawDiscordTimestamp::Legacy { time } => time,
+        };
+        Ok(Self::new(time))
+    }
+}
+
+/// Replaces every timestamp token in `text` with its [`DiscordTimestamp::display_plain`] text,
+/// for outputs that aren't rendered by Discord.
+pub fn markup_to_plain_text(text: &str, offset: UtcOffset, clock: &dyn Clock) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut copied_up_to = 0;
+    for (range, timestamp, display_mode) in DiscordTimestamp::find_markup(text) {
+        plain += &text[copied_up_to..range.start];
+        plain += &timestamp.display_plain(display_mode, offset, clock);
+        copied_up_to = range.end;
+    }
+    plain += &text[copied_up_to..];
+    plain
 }
 
 impl Display for DiscordTimestamp {
@@ -78,3 +312,146 @@ pub enum DiscordTimestampDisplay {
     FullDateTimeDayOfWeek,
     Relative,
 }
+
+impl DiscordTimestampDisplay {
+    /// The style letter in `<t:N:X>`.
+    pub fn identifier(&self) -> &'static str {
+        match self {
+            DiscordTimestampDisplay::ShortDate => "d",
+            DiscordTimestampDisplay::FullDate => "D",
+            DiscordTimestampDisplay::HourMinuteTime => "t",
+            DiscordTimestampDisplay::HourMinuteSecondTime => "T",
+            DiscordTimestampDisplay::FullDateTime => "f",
+            DiscordTimestampDisplay::FullDateTimeDayOfWeek => "F",
+            DiscordTimestampDisplay::Relative => "R",
+        }
+    }
+
+    pub fn from_identifier(identifier: &str) -> Option<Self> {
+        match identifier {
+            "d" => Some(DiscordTimestampDisplay::ShortDate),
+            "D" => Some(DiscordTimestampDisplay::FullDate),
+            "t" => Some(DiscordTimestampDisplay::HourMinuteTime),
+            "T" => Some(DiscordTimestampDisplay::HourMinuteSecondTime),
+            "f" => Some(DiscordTimestampDisplay::FullDateTime),
+            "F" => Some(DiscordTimestampDisplay::FullDateTimeDayOfWeek),
+            "R" => Some(DiscordTimestampDisplay::Relative),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::FixedClock;
+
+    use super::*;
+
+    /// Tuesday, November 14, 2023 10:13:20 PM UTC.
+    const TIME: i64 = 1_700_000_000;
+
+    fn plain(display_mode: DiscordTimestampDisplay, offset: UtcOffset) -> String {
+        let clock = FixedClock::from_unix_timestamp(TIME).unwrap();
+        DiscordTimestamp::new(TIME).display_plain(display_mode, offset, &clock)
+    }
+
+    #[test]
+    fn markup_round_trips() {
+        for display_mode in [
+            DiscordTimestampDisplay::ShortDate,
+            DiscordTimestampDisplay::FullDateTimeDayOfWeek,
+            DiscordTimestampDisplay::Relative,
+        ] {
+            let markup = DiscordTimestamp::new(TIME).display(display_mode);
+            assert_eq!(
+                DiscordTimestamp::parse_markup(&markup).unwrap(),
+                (DiscordTimestamp::new(TIME), display_mode)
+            );
+        }
+        assert_eq!(
+            DiscordTimestamp::parse_markup("<t:-5>").unwrap(),
+            (
+                DiscordTimestamp::new(-5),
+                DiscordTimestampDisplay::FullDateTime
+            )
+        );
+    }
+
+    #[test]
+    fn malformed_markup_is_rejected() {
+        for token in ["<t:>", "<t:12:Q>", "<t:1.5:R>", "t:1:R", "<t:1:R", "<x:1>"] {
+            assert!(DiscordTimestamp::parse_markup(token).is_err(), "{}", token);
+        }
+    }
+
+    #[test]
+    fn find_markup_skips_invalid_tokens() {
+        let text = "a <t:1:R> <t:x:R> <t:2> <t:3:Q>";
+        let found: Vec<_> = DiscordTimestamp::find_markup(text)
+            .into_iter()
+            .map(|(range, timestamp, _)| (&text[range], timestamp.unix_timestamp()))
+            .collect();
+
+        assert_eq!(found, [("<t:1:R>", 1), ("<t:2>", 2)]);
+    }
+
+    #[test]
+    fn plain_text_like_discord() {
+        use DiscordTimestampDisplay::*;
+
+        assert_eq!(plain(ShortDate, UtcOffset::UTC), "11/14/2023");
+        assert_eq!(plain(FullDate, UtcOffset::UTC), "November 14, 2023");
+        assert_eq!(plain(HourMinuteTime, UtcOffset::UTC), "10:13 PM");
+        assert_eq!(plain(HourMinuteSecondTime, UtcOffset::UTC), "10:13:20 PM");
+        assert_eq!(
+            plain(FullDateTimeDayOfWeek, UtcOffset::UTC),
+            "Tuesday, November 14, 2023 10:13 PM"
+        );
+        assert_eq!(
+            plain(FullDateTime, UtcOffset::from_hms(2, 0, 0).unwrap()),
+            "November 15, 2023 12:13 AM"
+        );
+    }
+
+    #[test]
+    fn relative_text() {
+        let clock = FixedClock::from_unix_timestamp(TIME).unwrap();
+        let relative = |secs: i64| {
+            DiscordTimestamp::new(TIME + secs).display_plain(
+                DiscordTimestampDisplay::Relative,
+                UtcOffset::UTC,
+                &clock,
+            )
+        };
+
+        assert_eq!(relative(3 * 3_600 + 59), "in 3 hours");
+        assert_eq!(relative(-86_400), "1 day ago");
+        assert_eq!(relative(-400 * 86_400), "1 year ago");
+        assert_eq!(relative(0), "in 0 seconds");
+    }
+
+    #[test]
+    fn markup_is_replaced_in_text() {
+        let clock = FixedClock::from_unix_timestamp(TIME).unwrap();
+
+        assert_eq!(
+            markup_to_plain_text("Ends <t:1700000000:d>, <t:x>!", UtcOffset::UTC, &clock),
+            "Ends 11/14/2023, <t:x>!"
+        );
+    }
+
+    #[test]
+    fn serializes_as_the_bare_number_and_reads_the_old_form() {
+        let timestamp = DiscordTimestamp::new(TIME);
+
+        assert_eq!(serde_json::to_string(&timestamp).unwrap(), "1700000000");
+        for json in ["1700000000", r#"{"time":1700000000}"#] {
+            let read: DiscordTimestamp = serde_json::from_str(json).unwrap();
+            assert_eq!(read.unix_timestamp(), TIME);
+        }
+        assert_eq!(
+            (DiscordTimestamp::new(i64::MIN) - DiscordTimestamp::new(1)).duration_in_seconds,
+            i64::MIN
+        );
+    }
+}
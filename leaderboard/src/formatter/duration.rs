@@ -1,10 +1,99 @@
 use std::cmp::Ordering;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::InvalidDuration;
+
+use super::discord_timestamp::DiscordTimestamp;
 
 const SECONDS_IN_A_MINUTE: i64 = 60;
 const SECONDS_IN_AN_HOUR: i64 = SECONDS_IN_A_MINUTE * 60;
 const SECONDS_IN_A_DAY: i64 = SECONDS_IN_AN_HOUR * 24;
 
-#[derive(Debug)]
+/// Anything this long (100 days) is "Too long" for [`Duration::format_too_long`].
+const TOO_LONG_SECONDS: i64 = 100 * SECONDS_IN_A_DAY;
+
+/// Which units a formatted [`Duration`] goes down to.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DurationPrecision {
+    /// Days and hours when non-zero, minutes always, e.g. "1d2h30m" or "0m".
+    #[default]
+    Minutes,
+    /// Like `Minutes`, plus seconds always, e.g. "2h0m15s".
+    Seconds,
+    /// The two most significant units starting at the largest non-zero one, e.g. "1d2h" or
+    /// "30m15s".
+    TwoUnits,
+}
+
+/// How a negative [`Duration`] is shown.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum NegativeStyle {
+    /// "-1h30m"
+    #[default]
+    Sign,
+    /// "1h30m overdue"
+    Overdue,
+}
+
+/// The words used when formatting a [`Duration`], so they can be translated.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DurationLabels {
+    pub day: String,
+    pub hour: String,
+    pub minute: String,
+    pub second: String,
+    pub overdue: String,
+    pub too_long: String,
+}
+
+impl Default for DurationLabels {
+    fn default() -> Self {
+        Self {
+            day: "d".to_string(),
+            hour: "h".to_string(),
+            minute: "m".to_string(),
+            second: "s".to_string(),
+            overdue: "overdue".to_string(),
+            too_long: "Too long".to_string(),
+        }
+    }
+}
+
+/// Options for [`Duration::format_with`]. The default matches [`Duration::format`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DurationFormat {
+    pub precision: DurationPrecision,
+    pub negative: NegativeStyle,
+    pub labels: DurationLabels,
+}
+
+impl DurationFormat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_precision(mut self, precision: DurationPrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    pub fn with_negative_style(mut self, negative: NegativeStyle) -> Self {
+        self.negative = negative;
+        self
+    }
+
+    pub fn with_labels(mut self, labels: DurationLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+}
+
+/// A whole number of seconds, serialized as the bare number.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Duration {
     pub duration_in_seconds: i64,
 }
@@ -17,43 +106,208 @@ impl Duration {
     }
 
     pub fn format(&self) -> String {
-        let (day, hour, min) = (self.calc_days(), self.calc_hours(), self.calc_minutes());
+        self.format_with(&DurationFormat::default())
+    }
 
-        let day_str = if day > 0 {
-            format!("{day}d")
-        } else {
-            "".to_string()
-        };
-        let hr_str = if hour > 0 {
-            format!("{hour}h")
-        } else {
-            "".to_string()
+    pub fn format_with(&self, format: &DurationFormat) -> String {
+        let labels = &format.labels;
+        let units = [
+            (self.calc_days(), &labels.day),
+            (self.calc_hours(), &labels.hour),
+            (self.calc_minutes(), &labels.minute),
+            (self.calc_seconds(), &labels.second),
+        ];
+
+        let shown: Vec<_> = match format.precision {
+            DurationPrecision::Minutes => units[..3]
+                .iter()
+                .enumerate()
+                .filter(|&(i, &(value, _))| value > 0 || i == 2)
+                .map(|(_, unit)| unit)
+                .collect(),
+            DurationPrecision::Seconds => units
+                .iter()
+                .enumerate()
+                .filter(|&(i, &(value, _))| value > 0 || i >= 2)
+                .map(|(_, unit)| unit)
+                .collect(),
+            DurationPrecision::TwoUnits => {
+                let first = units.iter().position(|&(value, _)| value > 0).unwrap_or(2);
+                units[first..(first + 2).min(units.len())]
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, &(value, _))| value > 0 || i == 0)
+                    .map(|(_, unit)| unit)
+                    .collect()
+            }
         };
+        let formatted: String = shown
+            .iter()
+            .map(|(value, label)| format!("{value}{label}"))
+            .collect();
 
-        format!("{day_str}{hr_str}{min}m")
+        match (self.duration_in_seconds < 0, format.negative) {
+            (false, _) => formatted,
+            (true, NegativeStyle::Sign) => format!("-{formatted}"),
+            (true, NegativeStyle::Overdue) => format!("{formatted} {}", labels.overdue),
+        }
     }
 
     pub fn format_too_long(&self) -> String {
-        if self.calc_days() >= 100 {
-            return "Too long".to_string();
+        self.format_within(Duration::new(TOO_LONG_SECONDS - 1))
+    }
+
+    /// Like [`Duration::format`], but anything longer than `limit` is "Too long".
+    pub fn format_within(&self, limit: Duration) -> String {
+        self.format_within_with(limit, &DurationFormat::default())
+    }
+
+    pub fn format_within_with(&self, limit: Duration, format: &DurationFormat) -> String {
+        if *self > limit {
+            return format.labels.too_long.clone();
+        }
+
+        self.format_with(format)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.duration_in_seconds < 0
+    }
+
+    // `unsigned_abs` because `i64::MIN` has no positive counterpart.
+    fn calc_days(&self) -> u64 {
+        self.duration_in_seconds.unsigned_abs() / SECONDS_IN_A_DAY as u64
+    }
+
+    fn calc_hours(&self) -> u64 {
+        self.duration_in_seconds.unsigned_abs() % SECONDS_IN_A_DAY as u64
+            / SECONDS_IN_AN_HOUR as u64
+    }
+
+    fn calc_minutes(&self) -> u64 {
+        self.duration_in_seconds.unsigned_abs() % SECONDS_IN_AN_HOUR as u64
+            / SECONDS_IN_A_MINUTE as u64
+    }
+
+    fn calc_seconds(&self) -> u64 {
+        self.duration_in_seconds.unsigned_abs() % SECONDS_IN_A_MINUTE as u64
+    }
+}
+
+/// Parses durations like "1d2h30m", "90m", "1h 30m" or "-45s".
+///
+/// # Details
+///
+/// Every number needs a unit (`d`, `h`, `m` or `s`, in any case), units may come in any order and
+/// repeated units add up. A leading `-` makes the whole duration negative.
+impl FromStr for Duration {
+    type Err = InvalidDuration;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidDuration(s.to_string());
+        let trimmed = s.trim();
+        let (sign, rest) = match trimmed.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, trimmed),
+        };
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+
+        let mut seconds = 0i64;
+        let mut number = String::new();
+        for c in rest.chars().filter(|c| !c.is_whitespace()) {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            let unit_seconds = match c.to_ascii_lowercase() {
+                'd' => SECONDS_IN_A_DAY,
+                'h' => SECONDS_IN_AN_HOUR,
+                'm' => SECONDS_IN_A_MINUTE,
+                's' => 1,
+                _ => return Err(invalid()),
+            };
+            let value: i64 = number.parse().map_err(|_| invalid())?;
+            seconds = value
+                .checked_mul(unit_seconds)
+                .and_then(|v| seconds.checked_add(v))
+                .ok_or_else(invalid)?;
+            number.clear();
+        }
+        if !number.is_empty() {
+            return Err(invalid());
         }
 
-        self.format()
+        Ok(Self::new(sign * seconds))
     }
+}
+
+impl From<time::Duration> for Duration {
+    fn from(duration: time::Duration) -> Self {
+        Self::new(duration.whole_seconds())
+    }
+}
+
+impl From<Duration> for time::Duration {
+    fn from(duration: Duration) -> Self {
+        time::Duration::seconds(duration.duration_in_seconds)
+    }
+}
 
-    fn calc_days(&self) -> i64 {
-        self.duration_in_seconds / SECONDS_IN_A_DAY
+impl From<std::time::Duration> for Duration {
+    fn from(duration: std::time::Duration) -> Self {
+        Self::new(duration.as_secs().try_into().unwrap_or(i64::MAX))
     }
+}
+
+impl Add for Duration {
+    type Output = Duration;
 
-    fn calc_hours(&self) -> i64 {
-        (self.duration_in_seconds - self.calc_days() * SECONDS_IN_A_DAY) / SECONDS_IN_AN_HOUR
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.duration_in_seconds
+                .saturating_add(rhs.duration_in_seconds),
+        )
     }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
 
-    fn calc_minutes(&self) -> i64 {
-        (self.duration_in_seconds
-            - self.calc_days() * SECONDS_IN_A_DAY
-            - self.calc_hours() * SECONDS_IN_AN_HOUR)
-            / SECONDS_IN_A_MINUTE
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.duration_in_seconds
+                .saturating_sub(rhs.duration_in_seconds),
+        )
+    }
+}
+
+impl SubAssign for Duration {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for Duration {
+    type Output = Duration;
+
+    fn neg(self) -> Self::Output {
+        Self::new(self.duration_in_seconds.saturating_neg())
+    }
+}
+
+impl Add<DiscordTimestamp> for Duration {
+    type Output = DiscordTimestamp;
+
+    fn add(self, rhs: DiscordTimestamp) -> Self::Output {
+        rhs + self
     }
 }
 
@@ -76,3 +330,120 @@ impl PartialEq for Duration {
 }
 
 impl Eq for Duration {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_HOURS_THIRTY: i64 = 2 * SECONDS_IN_AN_HOUR + 30 * SECONDS_IN_A_MINUTE;
+
+    fn format_with(secs: i64, precision: DurationPrecision) -> String {
+        Duration::new(secs).format_with(&DurationFormat::new().with_precision(precision))
+    }
+
+    #[test]
+    fn format_shows_minutes_always() {
+        assert_eq!(
+            Duration::new(SECONDS_IN_A_DAY + TWO_HOURS_THIRTY).format(),
+            "1d2h30m"
+        );
+        assert_eq!(Duration::new(SECONDS_IN_A_DAY).format(), "1d0m");
+        assert_eq!(Duration::new(59).format(), "0m");
+    }
+
+    #[test]
+    fn precisions() {
+        assert_eq!(format_with(7215, DurationPrecision::Seconds), "2h0m15s");
+        assert_eq!(
+            format_with(
+                SECONDS_IN_A_DAY + TWO_HOURS_THIRTY,
+                DurationPrecision::TwoUnits
+            ),
+            "1d2h"
+        );
+        assert_eq!(format_with(1815, DurationPrecision::TwoUnits), "30m15s");
+        assert_eq!(format_with(3600, DurationPrecision::TwoUnits), "1h");
+        assert_eq!(format_with(0, DurationPrecision::TwoUnits), "0m");
+    }
+
+    #[test]
+    fn negative_styles() {
+        let overdue = DurationFormat::new().with_negative_style(NegativeStyle::Overdue);
+
+        assert_eq!(Duration::new(-5400).format(), "-1h30m");
+        assert_eq!(Duration::new(-5400).format_with(&overdue), "1h30m overdue");
+        assert_eq!(Duration::new(5400).format_with(&overdue), "1h30m");
+    }
+
+    #[test]
+    fn too_long_past_the_limit() {
+        assert_eq!(
+            Duration::new(TOO_LONG_SECONDS).format_too_long(),
+            "Too long"
+        );
+        assert_eq!(
+            Duration::new(TOO_LONG_SECONDS - SECONDS_IN_A_DAY).format_too_long(),
+            "99d0m"
+        );
+        assert_eq!(
+            Duration::new(61).format_within(Duration::new(60)),
+            "Too long"
+        );
+    }
+
+    #[test]
+    fn parses_units_in_any_order_and_case() {
+        let parse = |s: &str| s.parse::<Duration>().unwrap().duration_in_seconds;
+
+        assert_eq!(parse("1d2h30m"), SECONDS_IN_A_DAY + TWO_HOURS_THIRTY);
+        assert_eq!(parse(" 30m 2H "), TWO_HOURS_THIRTY);
+        assert_eq!(parse("1h1h"), 2 * SECONDS_IN_AN_HOUR);
+        assert_eq!(parse("-45s"), -45);
+    }
+
+    #[test]
+    fn rejects_malformed_durations() {
+        for s in [
+            "",
+            "-",
+            "5",
+            "h",
+            "1x",
+            "1.5h",
+            "--1m",
+            "99999999999999999999d",
+        ] {
+            assert!(s.parse::<Duration>().is_err(), "{:?} parsed", s);
+        }
+    }
+
+    #[test]
+    fn formatting_parses_back() {
+        for secs in [0, 59 * 60, TWO_HOURS_THIRTY, 3 * SECONDS_IN_A_DAY + 60] {
+            let formatted = Duration::new(secs).format();
+            assert_eq!(formatted.parse::<Duration>().unwrap(), Duration::new(secs));
+        }
+    }
+
+    #[test]
+    fn converts_to_and_from_time_durations() {
+        let duration = Duration::new(-90);
+
+        assert_eq!(Duration::from(time::Duration::from(duration)), duration);
+        assert_eq!(
+            Duration::from(std::time::Duration::from_millis(90_500)),
+            Duration::new(90)
+        );
+        assert_eq!(-duration + Duration::new(10), Duration::new(100));
+    }
+
+    #[test]
+    fn extremes_saturate_instead_of_overflowing() {
+        let (min, max) = (Duration::new(i64::MIN), Duration::new(i64::MAX));
+
+        assert_eq!(min.format(), "-106751991167300d15h30m");
+        assert_eq!(max + Duration::new(1), max);
+        assert_eq!(min - Duration::new(1), min);
+        assert_eq!(-min, max);
+    }
+}
@@ -1,8 +1,11 @@
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
 use thiserror::Error;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 #[derive(Debug, Copy, Clone, Default, Error, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum StringAlignment {
     Left,
     Centered,
@@ -17,18 +20,273 @@ impl Display for StringAlignment {
     }
 }
 
+/// What to do with a value wider than its column.
+#[derive(Debug, Copy, Clone, Default, Error, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overflow {
+    /// Leave it as is and let it push the rest of the row out.
+    #[default]
+    Extend,
+    /// Cut it off with an ellipsis, e.g. "Speedr…".
+    Truncate,
+    /// Break it over several lines, at spaces where possible.
+    Wrap,
+    /// Keep the start and the end and elide the middle, e.g. "Spe…run".
+    Abbreviate,
+}
+
+impl Display for Overflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// How many terminal columns `s` takes up, counting wide characters (CJK, most emoji) as two.
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// How wide `s` looks on Discord: links count as their label and backticks don't count.
+pub fn visible_width(s: &str) -> usize {
+    width_of(&tokens(s))
+}
+
+/// Replaces every `[label](<url>)` or `[label](url)` link in `text` with `link_fn(label, url)`.
+///
+/// # Details
+///
+/// A bare URL may contain balanced parentheses, e.g. `[Egg](https://x.org/wiki/Egg_(food))`; an
+/// `<url>` may contain anything but `>`.
+pub fn replace_links(text: &str, link_fn: impl Fn(&str, &str) -> String) -> String {
+    let mut replaced = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some((range, label, url)) = next_link(rest) {
+        replaced += &rest[..range.start];
+        replaced += &link_fn(label, url);
+        rest = &rest[range.end..];
+    }
+    replaced + rest
+}
+
+/// Where the first link in `text` is, with its label and URL.
+fn next_link(text: &str) -> Option<(Range<usize>, &str, &str)> {
+    let mut from = 0;
+    while let Some(start) = text[from..].find('[').map(|i| from + i) {
+        let link = text[start + 1..]
+            .split_once("](")
+            .filter(|(label, _)| !label.contains(['[', ']']))
+            .and_then(|(label, after)| {
+                let (url, after) = split_link_target(after)?;
+                Some((label, url, after))
+            });
+        if let Some((label, url, after)) = link {
+            return Some((start..text.len() - after.len(), label, url));
+        }
+        from = start + 1;
+    }
+    None
+}
+
+/// Splits the part of a link after `](` into its URL and the text after the closing `)`.
+fn split_link_target(target: &str) -> Option<(&str, &str)> {
+    if let Some(target) = target.strip_prefix('<') {
+        return target.split_once(">)");
+    }
+
+    let mut depth = 0;
+    for (i, c) in target.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some((&target[..i], &target[i + 1..])),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// A piece of Discord-flavoured text that is never split: a whole link, a backtick or a single
+/// character, with how wide it looks.
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    width: usize,
+}
+
+impl Token<'_> {
+    fn is_backtick(&self) -> bool {
+        self.text == "`"
+    }
+}
+
+fn tokens(s: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = s;
+    loop {
+        let link = next_link(rest);
+        let plain = &rest[..link
+            .as_ref()
+            .map_or(rest.len(), |(range, _, _)| range.start)];
+        tokens.extend(plain.char_indices().map(|(i, c)| Token {
+            text: &plain[i..i + c.len_utf8()],
+            width: if c == '`' { 0 } else { c.width().unwrap_or(0) },
+        }));
+
+        match link {
+            Some((range, label, _)) => {
+                tokens.push(Token {
+                    text: &rest[range.clone()],
+                    width: display_width(&label.replace('`', "")),
+                });
+                rest = &rest[range.end..];
+            }
+            None => return tokens,
+        }
+    }
+}
+
+fn width_of(tokens: &[Token]) -> usize {
+    tokens.iter().map(|t| t.width).sum()
+}
+
+fn concat(tokens: &[Token]) -> String {
+    tokens.iter().map(|t| t.text).collect()
+}
+
+/// The backticks among `tokens`, so cutting text out doesn't change where code spans start and
+/// end in the rest of the row.
+fn backticks(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .filter(|t| t.is_backtick())
+        .map(|t| t.text)
+        .collect()
+}
+
+/// How many of `tokens`, from the start, fit into `width` columns.
+fn fitting<'a>(tokens: impl IntoIterator<Item = Token<'a>>, width: usize) -> usize {
+    let mut taken = 0;
+    tokens
+        .into_iter()
+        .take_while(|t| {
+            taken += t.width;
+            taken <= width
+        })
+        .count()
+}
+
+/// Fits `s` into `width` columns according to `overflow`, one entry per line.
+///
+/// # Details
+///
+/// Widths are [`visible_width`]s. Links are kept whole, and backticks in text that gets cut out
+/// are kept, so the code spans in the rest of the row stay the same.
+pub fn fit(s: &str, width: usize, overflow: Overflow) -> Vec<String> {
+    let tokens = tokens(s);
+    if width_of(&tokens) <= width {
+        return vec![s.to_string()];
+    }
+
+    match overflow {
+        Overflow::Extend => vec![s.to_string()],
+        Overflow::Truncate => {
+            let head = fitting(tokens.iter().copied(), width.saturating_sub(1));
+            vec![concat(&tokens[..head]) + "\u{2026}" + &backticks(&tokens[head..])]
+        }
+        Overflow::Abbreviate => {
+            let room = width.saturating_sub(1);
+            let head = fitting(tokens.iter().copied(), room - room / 2);
+            let tail = tokens.len() - fitting(tokens.iter().rev().copied(), room / 2);
+            vec![
+                concat(&tokens[..head])
+                    + "\u{2026}"
+                    + &backticks(&tokens[head..tail])
+                    + &concat(&tokens[tail..]),
+            ]
+        }
+        Overflow::Wrap => wrap(&tokens, width.max(1)),
+    }
+}
+
+/// Breaks `tokens` into lines of at most `width`, at spaces where possible.
+fn wrap(tokens: &[Token], width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line: Vec<Token> = vec![];
+    for word in tokens.split(|t| t.text == " ") {
+        let candidate_width = match line.is_empty() {
+            true => width_of(word),
+            false => width_of(&line) + 1 + width_of(word),
+        };
+        if candidate_width <= width {
+            if !line.is_empty() {
+                line.push(Token {
+                    text: " ",
+                    width: 1,
+                });
+            }
+            line.extend(word);
+            continue;
+        }
+
+        if !line.is_empty() {
+            lines.push(concat(&std::mem::take(&mut line)));
+        }
+        let mut rest = word;
+        while width_of(rest) > width {
+            // A single token wider than the line gets a line of its own.
+            let head = fitting(rest.iter().copied(), width).max(1);
+            lines.push(concat(&rest[..head]));
+            rest = &rest[head..];
+        }
+        line = rest.to_vec();
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(concat(&line));
+    }
+    lines
+}
+
+/// Egg amounts with the game's suffixes, e.g. "12.3q" for 1.23e16.
+pub fn egg_amount(amount: f64) -> String {
+    const SUFFIXES: [&str; 13] = [
+        "", "K", "M", "B", "T", "q", "Q", "s", "S", "o", "N", "d", "U",
+    ];
+    let mut scaled = amount;
+    let mut suffix = 0;
+    while scaled.abs() >= 1000.0 && suffix < SUFFIXES.len() - 1 {
+        scaled /= 1000.0;
+        suffix += 1;
+    }
+    match suffix {
+        0 => format!("{:.0}", scaled),
+        _ => format!("{:.1}{}", scaled, SUFFIXES[suffix]),
+    }
+}
+
+fn pad(s: String, left: usize, right: usize) -> String {
+    format!("{}{}{}", " ".repeat(left), s, " ".repeat(right))
+}
+
 pub fn left_align(s: impl Into<String>, width: usize) -> String {
-    format!("{:<width$}", s.into())
+    let s = s.into();
+    let padding = width.saturating_sub(visible_width(&s));
+    pad(s, 0, padding)
 }
 
 pub fn right_align(s: impl Into<String>, width: usize) -> String {
-    format!("{:>width$}", s.into())
+    let s = s.into();
+    let padding = width.saturating_sub(visible_width(&s));
+    pad(s, padding, 0)
 }
 
 pub fn center_align(s: impl Into<String>, width: usize) -> String {
-    format!("{:^width$}", s.into())
+    let s = s.into();
+    let padding = width.saturating_sub(visible_width(&s));
+    pad(s, padding / 2, padding - padding / 2)
 }
 
+/// Pads `s` with spaces to `width` columns as measured by [`visible_width`]. Wider text is left
+/// as is.
 pub fn align(s: impl Into<String>, width: usize, alignment: StringAlignment) -> String {
     match alignment {
         StringAlignment::Left => left_align(s, width),
@@ -37,3 +295,117 @@ pub fn align(s: impl Into<String>, width: usize, alignment: StringAlignment) ->
         StringAlignment::None => s.into(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown_link(label: &str, url: &str) -> String {
+        format!("{label} <{url}>")
+    }
+
+    #[test]
+    fn replace_links_handles_both_url_forms() {
+        assert_eq!(
+            replace_links("a [x](<https://a.b/c>) b [y](https://d.e) c", markdown_link),
+            "a x <https://a.b/c> b y <https://d.e> c"
+        );
+    }
+
+    #[test]
+    fn replace_links_keeps_parentheses_in_urls() {
+        assert_eq!(
+            replace_links("[Egg](https://x.org/wiki/Egg_(food)) done", markdown_link),
+            "Egg <https://x.org/wiki/Egg_(food)> done"
+        );
+        assert_eq!(
+            replace_links("[Egg](<https://x.org/a)b>) done", markdown_link),
+            "Egg <https://x.org/a)b> done"
+        );
+    }
+
+    #[test]
+    fn replace_links_leaves_other_brackets_alone() {
+        assert_eq!(
+            replace_links("[departed] [x](u)", markdown_link),
+            "[departed] x <u>"
+        );
+        assert_eq!(
+            replace_links("[x](unclosed [y", markdown_link),
+            "[x](unclosed [y"
+        );
+    }
+
+    #[test]
+    fn widths_count_wide_characters_and_skip_markup() {
+        assert_eq!(display_width("é日本"), 5);
+        assert_eq!(visible_width("[日本](<https://a.b/(c)>) `x`"), 6);
+    }
+
+    #[test]
+    fn align_pads_to_visible_width() {
+        assert_eq!(align("日本", 6, StringAlignment::Left), "日本  ");
+        assert_eq!(align("é", 4, StringAlignment::Right), "   é");
+        assert_eq!(align("`ab", 5, StringAlignment::Centered), " `ab  ");
+        assert_eq!(
+            align("[x](<u>)", 3, StringAlignment::Centered),
+            " [x](<u>) "
+        );
+        assert_eq!(align("toolong", 3, StringAlignment::Left), "toolong");
+        assert_eq!(align("ab", 5, StringAlignment::None), "ab");
+    }
+
+    #[test]
+    fn fit_leaves_text_that_fits() {
+        let link = "[\u{29c9}](<https://a.b/c>) `abcdef";
+        assert_eq!(fit(link, 8, Overflow::Truncate), [link]);
+        assert_eq!(fit("toolong", 3, Overflow::Extend), ["toolong"]);
+    }
+
+    #[test]
+    fn truncate_keeps_links_whole_and_backticks() {
+        assert_eq!(fit("Speedrun", 7, Overflow::Truncate), ["Speedr\u{2026}"]);
+        assert_eq!(fit("日本語です", 6, Overflow::Truncate), ["日本\u{2026}"]);
+        assert_eq!(
+            fit("ab[link](<https://a.b>)", 4, Overflow::Truncate),
+            ["ab\u{2026}"]
+        );
+        assert_eq!(
+            fit("[\u{29c9}](<https://a.b/c>) `abcdef", 5, Overflow::Truncate),
+            ["[\u{29c9}](<https://a.b/c>) `ab\u{2026}"]
+        );
+        assert_eq!(fit("ab`cdef`", 4, Overflow::Truncate), ["ab`c\u{2026}`"]);
+    }
+
+    #[test]
+    fn abbreviate_keeps_both_ends() {
+        assert_eq!(fit("Speedrun", 7, Overflow::Abbreviate), ["Spe\u{2026}run"]);
+        assert_eq!(fit("ééééé", 3, Overflow::Abbreviate), ["é\u{2026}é"]);
+        assert_eq!(
+            fit("a`bcdef`[x](<u>)", 4, Overflow::Abbreviate),
+            ["a`b\u{2026}`[x](<u>)"]
+        );
+    }
+
+    #[test]
+    fn wrap_breaks_at_spaces_then_anywhere() {
+        assert_eq!(
+            fit("one two three", 7, Overflow::Wrap),
+            ["one two", "three"]
+        );
+        assert_eq!(fit("abcdefghij", 4, Overflow::Wrap), ["abcd", "efgh", "ij"]);
+        assert_eq!(
+            fit("日本語 です", 4, Overflow::Wrap),
+            ["日本", "語", "です"]
+        );
+        assert_eq!(fit("日本", 1, Overflow::Wrap), ["日", "本"]);
+    }
+
+    #[test]
+    fn wrap_keeps_links_whole() {
+        assert_eq!(
+            fit("see [a long label](<https://a.b>) ok", 6, Overflow::Wrap),
+            ["see", "[a long label](<https://a.b>)", "ok"]
+        );
+    }
+}
@@ -0,0 +1,678 @@
+use std::sync::Arc;
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use time::UtcOffset;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::PageOverflow;
+
+use super::discord_timestamp;
+use super::string_formatter::{self, Overflow, StringAlignment};
+
+/// How a column is laid out, independent of the output format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnLayout {
+    pub name: String,
+    pub width: usize,
+    pub alignment: StringAlignment,
+    pub overflow: Overflow,
+}
+
+/// A table with every cell already formatted, ready for a [`TableRenderer`].
+///
+/// # Details
+///
+/// `numbers` has the same shape as `rows` and holds the number behind a cell, e.g. a duration in
+/// seconds or a timestamp in unix seconds, for formats with types. Cells without one, and rows
+/// past the end of `numbers`, are text.
+///
+/// `aggregate_rows` summarise the data (totals, medians, ...). Formats with text around the table
+/// show them under the rows; data-only formats leave them out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableCells {
+    pub columns: Vec<ColumnLayout>,
+    pub rows: Vec<Vec<String>>,
+    pub numbers: Vec<Vec<Option<f64>>>,
+    pub aggregate_rows: Vec<Vec<String>>,
+}
+
+impl TableCells {
+    /// The number behind the cell in `row` and `column`, if there is one.
+    pub fn number(&self, row: usize, column: usize) -> Option<f64> {
+        self.numbers.get(row)?.get(column).copied().flatten()
+    }
+
+    /// The data rows, then the aggregate rows.
+    pub fn rows_with_aggregates(&self) -> impl Iterator<Item = &Vec<String>> {
+        self.rows.iter().chain(&self.aggregate_rows)
+    }
+
+    /// Just the rows in `range`, with the same columns, and the aggregate rows if `aggregates`.
+    fn slice(&self, range: std::ops::Range<usize>, aggregates: bool) -> Self {
+        Self {
+            columns: self.columns.clone(),
+            numbers: self
+                .numbers
+                .get(range.clone())
+                .map(<[_]>::to_vec)
+                .unwrap_or_default(),
+            rows: self.rows[range].to_vec(),
+            aggregate_rows: match aggregates {
+                true => self.aggregate_rows.clone(),
+                false => vec![],
+            },
+        }
+    }
+}
+
+/// Turns a [`TableCells`] into one output format.
+///
+/// # Details
+///
+/// Cells are written for Discord, so they can contain backticks, `[label](<url>)` links and
+/// `<t:N:X>` timestamps. Every other renderer converts those into something that makes sense in
+/// its format. Data-only formats (CSV, JSON) don't take any text around the table.
+pub trait TableRenderer {
+    fn render_table(&self, table: &TableCells) -> String;
+
+    /// Whether text around the table (titles, notes, footers) belongs in this format.
+    fn supports_text(&self) -> bool {
+        true
+    }
+
+    /// Converts Discord-flavoured text around the table for this format.
+    fn render_text(&self, text: &str) -> String {
+        text.to_string()
+    }
+
+    /// The most one message can hold, for formats that are posted as messages and have to be
+    /// split with [`paginate`]. `None` for formats that are written out whole.
+    fn message_limit(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Discord's limit on the length of a single message.
+pub const DISCORD_MESSAGE_LIMIT: usize = 2000;
+
+/// How long `text` is as far as message limits go: UTF-16 code units, like Discord counts them.
+/// Never less than the number of characters, so a budget in characters holds either way.
+pub fn message_length(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+/// Splits `table` into pages of at most `budget` (see [`message_length`]), each rendered with
+/// `renderer`.
+///
+/// # Details
+///
+/// Pages break between rows and each one repeats the column header. `preamble` only goes on the
+/// first page, and the aggregate rows and `footer` only on the last; if they don't fit after the
+/// last rows, rows move to a new page to make room. Fails if a single row can't fit a page on its
+/// own.
+pub fn paginate(
+    renderer: &dyn TableRenderer,
+    table: &TableCells,
+    preamble: &str,
+    footer: &str,
+    budget: usize,
+) -> Result<Vec<String>, PageOverflow> {
+    let render_page = |rows: std::ops::Range<usize>, first: bool, last: bool| {
+        format!(
+            "{}{}{}",
+            if first { preamble } else { "" },
+            renderer.render_table(&table.slice(rows, last)),
+            if last { footer } else { "" }
+        )
+    };
+    let fits = |page: &str| message_length(page) <= budget;
+
+    let rows = &table.rows;
+    let mut pages = vec![];
+    let mut start = 0;
+    loop {
+        let first = pages.is_empty();
+        let mut end = start;
+        while end < rows.len() && fits(&render_page(start..end + 1, first, false)) {
+            end += 1;
+        }
+
+        if end == rows.len() {
+            let last_page = render_page(start..rows.len(), first, true);
+            if fits(&last_page) {
+                pages.push(last_page);
+                return Ok(pages);
+            }
+            // Leave at least one row for the last page, unless this page would be empty.
+            if end - start > 1 {
+                end -= 1;
+            }
+        }
+        if end == start {
+            let needed = match rows.get(start) {
+                Some(_) => render_page(start..start + 1, first, false),
+                None => render_page(start..start, first, true),
+            };
+            return Err(PageOverflow {
+                budget,
+                needed: message_length(&needed),
+            });
+        }
+
+        pages.push(render_page(start..end, first, false));
+        start = end;
+    }
+}
+
+/// Looks a renderer up by name: "discord", "markdown", "csv", "json" or "plain".
+pub fn renderer_by_name(name: &str) -> Option<Box<dyn TableRenderer>> {
+    match name.to_lowercase().as_str() {
+        "discord" => Some(Box::new(DiscordRenderer)),
+        "markdown" | "md" => Some(Box::new(MarkdownRenderer::new())),
+        "csv" => Some(Box::new(CsvRenderer::new())),
+        "json" => Some(Box::new(JsonRenderer::new())),
+        "plain" | "text" => Some(Box::new(PlainTextRenderer::new())),
+        _ => None,
+    }
+}
+
+/// How Discord markup is turned into plain text for the non-Discord renderers.
+#[derive(Debug, Clone)]
+pub struct PlainTextOptions {
+    /// Timezone timestamps are shown in.
+    pub offset: UtcOffset,
+    /// What relative timestamps are relative to.
+    pub clock: Arc<dyn Clock>,
+}
+
+impl PlainTextOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_offset(mut self, offset: UtcOffset) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Replaces timestamps with their text and drops backticks and italics markers. Links become
+    /// `link_fn(label, url)`.
+    fn convert(&self, text: &str, link_fn: impl Fn(&str, &str) -> String) -> String {
+        let text = discord_timestamp::markup_to_plain_text(text, self.offset, self.clock.as_ref());
+        let text = string_formatter::replace_links(&text, link_fn);
+        text.replace(['`', '*'], "").trim().to_string()
+    }
+}
+
+impl Default for PlainTextOptions {
+    fn default() -> Self {
+        Self {
+            offset: UtcOffset::UTC,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+/// The original backtick layout: a code-block header and `|`-separated rows.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiscordRenderer;
+
+impl DiscordRenderer {
+    pub fn render_header(&self, table: &TableCells) -> String {
+        let col_names = table
+            .columns
+            .iter()
+            .map(|c| string_formatter::align(c.name.clone(), c.width, c.alignment))
+            .collect::<Vec<_>>();
+
+        format!("`{}`", col_names.join("|"))
+    }
+
+    pub fn render_body(&self, table: &TableCells) -> String {
+        let mut body = String::new();
+        for row in table.rows_with_aggregates() {
+            for line in fit_row(&table.columns, row) {
+                body += &format!("{}\n", line.join("|"));
+            }
+        }
+
+        body
+    }
+}
+
+/// Fits and aligns every cell of `row` to its column, one entry per line of output; more than one
+/// if a column wraps.
+fn fit_row(columns: &[ColumnLayout], row: &[String]) -> Vec<Vec<String>> {
+    let cells = columns
+        .iter()
+        .zip(row)
+        .map(|(c, cell)| string_formatter::fit(cell, c.width, c.overflow))
+        .collect::<Vec<_>>();
+    let height = cells.iter().map(|lines| lines.len()).max().unwrap_or(1);
+
+    (0..height)
+        .map(|i| {
+            columns
+                .iter()
+                .zip(&cells)
+                .map(|(c, lines)| {
+                    let line = lines.get(i).cloned().unwrap_or_default();
+                    string_formatter::align(line, c.width, c.alignment)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+impl TableRenderer for DiscordRenderer {
+    fn render_table(&self, table: &TableCells) -> String {
+        format!("{}\n{}", self.render_header(table), self.render_body(table))
+    }
+
+    fn message_limit(&self) -> Option<usize> {
+        Some(DISCORD_MESSAGE_LIMIT)
+    }
+}
+
+/// A GitHub-flavoured Markdown table.
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownRenderer {
+    options: PlainTextOptions,
+}
+
+impl MarkdownRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(mut self, options: PlainTextOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn cell(&self, text: &str) -> String {
+        self.options
+            .convert(text, |label, url| format!("[{label}]({url})"))
+            .replace('|', "\\|")
+    }
+}
+
+impl TableRenderer for MarkdownRenderer {
+    fn render_table(&self, table: &TableCells) -> String {
+        let header = table
+            .columns
+            .iter()
+            .map(|c| self.cell(&c.name))
+            .collect::<Vec<_>>();
+        let separator = table
+            .columns
+            .iter()
+            .map(|c| match c.alignment {
+                StringAlignment::Left => ":---",
+                StringAlignment::Centered => ":---:",
+                StringAlignment::Right => "---:",
+                StringAlignment::None => "---",
+            })
+            .collect::<Vec<_>>();
+
+        let mut rendered = format!(
+            "| {} |\n| {} |\n",
+            header.join(" | "),
+            separator.join(" | ")
+        );
+        for row in table.rows_with_aggregates() {
+            let cells = row.iter().map(|cell| self.cell(cell)).collect::<Vec<_>>();
+            rendered += &format!("| {} |\n", cells.join(" | "));
+        }
+        rendered
+    }
+
+    fn render_text(&self, text: &str) -> String {
+        discord_timestamp::markup_to_plain_text(
+            text,
+            self.options.offset,
+            self.options.clock.as_ref(),
+        )
+    }
+}
+
+/// RFC 4180 CSV with a header row. Links and aggregate rows are dropped.
+#[derive(Debug, Clone, Default)]
+pub struct CsvRenderer {
+    options: PlainTextOptions,
+}
+
+impl CsvRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(mut self, options: PlainTextOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn field(&self, text: &str) -> String {
+        let text = self.options.convert(text, |_, _| "".to_string());
+        if text.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text
+        }
+    }
+}
+
+impl TableRenderer for CsvRenderer {
+    fn render_table(&self, table: &TableCells) -> String {
+        let mut rendered = table
+            .columns
+            .iter()
+            .map(|c| self.field(&c.name))
+            .collect::<Vec<_>>()
+            .join(",")
+            + "\r\n";
+        for row in &table.rows {
+            rendered += &row
+                .iter()
+                .map(|cell| self.field(cell))
+                .collect::<Vec<_>>()
+                .join(",");
+            rendered += "\r\n";
+        }
+        rendered
+    }
+
+    fn supports_text(&self) -> bool {
+        false
+    }
+}
+
+/// An array with one object per row, keyed by column name in column order. Cells with a number
+/// behind them (see [`TableCells::numbers`]) are JSON numbers, the rest strings. Links and
+/// aggregate rows are dropped.
+#[derive(Debug, Clone, Default)]
+pub struct JsonRenderer {
+    options: PlainTextOptions,
+}
+
+impl JsonRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(mut self, options: PlainTextOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn value(&self, cell: &str, number: Option<f64>) -> serde_json::Value {
+        match number {
+            Some(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => (n as i64).into(),
+            Some(n) => serde_json::Number::from_f64(n).map_or(serde_json::Value::Null, Into::into),
+            None => self.options.convert(cell, |_, _| "".to_string()).into(),
+        }
+    }
+}
+
+/// A JSON object that keeps its keys in the order they were added.
+struct JsonObject<'a>(Vec<(&'a str, serde_json::Value)>);
+
+impl Serialize for JsonObject<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl TableRenderer for JsonRenderer {
+    fn render_table(&self, table: &TableCells) -> String {
+        let rows = table
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                JsonObject(
+                    table
+                        .columns
+                        .iter()
+                        .zip(row)
+                        .enumerate()
+                        .map(|(j, (c, cell))| {
+                            (c.name.as_str(), self.value(cell, table.number(i, j)))
+                        })
+                        .collect(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::to_string_pretty(&rows).expect("JSON values always serialize")
+    }
+
+    fn supports_text(&self) -> bool {
+        false
+    }
+}
+
+/// Fixed-width text with a dashed line under the header, for logs and terminals.
+#[derive(Debug, Clone, Default)]
+pub struct PlainTextRenderer {
+    options: PlainTextOptions,
+}
+
+impl PlainTextRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(mut self, options: PlainTextOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn cell(&self, text: &str) -> String {
+        self.options.convert(text, |_, _| "".to_string())
+    }
+}
+
+impl TableRenderer for PlainTextRenderer {
+    fn render_table(&self, table: &TableCells) -> String {
+        let rows = table
+            .rows_with_aggregates()
+            .map(|row| row.iter().map(|cell| self.cell(cell)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        // Columns that don't limit their values grow to fit them instead of pushing the row out.
+        let columns = table
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let mut c = c.clone();
+                if c.overflow == Overflow::Extend {
+                    c.width = rows
+                        .iter()
+                        .filter_map(|row| row.get(i))
+                        .map(|cell| string_formatter::visible_width(cell))
+                        .fold(
+                            c.width.max(string_formatter::visible_width(&c.name)),
+                            usize::max,
+                        );
+                }
+                c
+            })
+            .collect::<Vec<_>>();
+
+        let render_line = |line: Vec<String>| line.join(" | ").trim_end().to_string() + "\n";
+        let header = columns
+            .iter()
+            .map(|c| string_formatter::align(c.name.clone(), c.width, c.alignment))
+            .collect();
+
+        let mut rendered = render_line(header);
+        rendered += &columns
+            .iter()
+            .map(|c| "-".repeat(c.width))
+            .collect::<Vec<_>>()
+            .join("-+-");
+        rendered += "\n";
+        for row in &rows {
+            for line in fit_row(&columns, row) {
+                rendered += &render_line(line);
+            }
+        }
+        rendered
+    }
+
+    fn render_text(&self, text: &str) -> String {
+        text.split('\n')
+            .map(|line| {
+                self.options
+                    .convert(line, |label, url| format!("{label} ({url})"))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> TableCells {
+        let column = |name: &str| ColumnLayout {
+            name: name.to_string(),
+            width: 6,
+            alignment: StringAlignment::Left,
+            overflow: Overflow::Extend,
+        };
+        TableCells {
+            columns: vec![column("Zulu"), column("Alpha"), column("Mike")],
+            rows: vec![
+                vec![
+                    "[\u{29c9}](<https://a.b/c>) `abc".to_string(),
+                    "1h 30m".to_string(),
+                    "12.5%".to_string(),
+                ],
+                vec!["def".to_string(), "3h".to_string(), "".to_string()],
+            ],
+            numbers: vec![vec![None, Some(5400.0), Some(12.5)]],
+            aggregate_rows: vec![vec![
+                "Total".to_string(),
+                "4h 30m".to_string(),
+                "".to_string(),
+            ]],
+        }
+    }
+
+    #[test]
+    fn json_keeps_column_order_and_types() {
+        let json = JsonRenderer::new().render_table(&table());
+
+        assert_eq!(
+            json.lines().map(str::trim).collect::<Vec<_>>(),
+            [
+                "[",
+                "{",
+                "\"Zulu\": \"abc\",",
+                "\"Alpha\": 5400,",
+                "\"Mike\": 12.5",
+                "},",
+                "{",
+                "\"Zulu\": \"def\",",
+                "\"Alpha\": \"3h\",",
+                "\"Mike\": \"\"",
+                "}",
+                "]",
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_drops_links_and_aggregates_and_quotes_fields() {
+        let mut table = table();
+        table.rows[1][2] = "a, \"b\"".to_string();
+
+        assert_eq!(
+            CsvRenderer::new().render_table(&table),
+            "Zulu,Alpha,Mike\r\nabc,1h 30m,12.5%\r\ndef,3h,\"a, \"\"b\"\"\"\r\n"
+        );
+    }
+
+    #[test]
+    fn markdown_keeps_links_and_aggregates() {
+        let rendered = MarkdownRenderer::new().render_table(&table());
+
+        assert_eq!(
+            rendered.lines().nth(2),
+            Some("| [\u{29c9}](https://a.b/c) abc | 1h 30m | 12.5% |")
+        );
+        assert_eq!(rendered.lines().last(), Some("| Total | 4h 30m |  |"));
+    }
+
+    fn numbered(rows: usize) -> TableCells {
+        TableCells {
+            columns: vec![ColumnLayout {
+                name: "N".to_string(),
+                width: 3,
+                alignment: StringAlignment::Left,
+                overflow: Overflow::Extend,
+            }],
+            rows: (1..=rows).map(|i| vec![i.to_string()]).collect(),
+            numbers: vec![],
+            aggregate_rows: vec![],
+        }
+    }
+
+    #[test]
+    fn pages_repeat_the_header_and_keep_the_text_at_the_ends() {
+        let pages = paginate(&DiscordRenderer, &numbered(5), "P\n", "F\n", 16).unwrap();
+
+        assert_eq!(
+            pages,
+            [
+                "P\n`N  `\n1  \n2  \n",
+                "`N  `\n3  \n4  \n",
+                "`N  `\n5  \nF\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn rows_make_room_for_the_footer() {
+        let pages = paginate(&DiscordRenderer, &numbered(2), "", "F\n", 15).unwrap();
+
+        assert_eq!(pages, ["`N  `\n1  \n", "`N  `\n2  \nF\n"]);
+    }
+
+    #[test]
+    fn aggregates_stay_on_the_last_page() {
+        let table = TableCells {
+            aggregate_rows: vec![vec!["T".to_string()]],
+            ..numbered(3)
+        };
+        let pages = paginate(&DiscordRenderer, &table, "", "F\n", 18).unwrap();
+
+        assert_eq!(pages, ["`N  `\n1  \n2  \n", "`N  `\n3  \nT  \nF\n"]);
+    }
+
+    #[test]
+    fn a_row_too_long_for_a_page_is_an_error() {
+        let err = paginate(&DiscordRenderer, &numbered(1), "", "", 5).unwrap_err();
+
+        assert_eq!((err.budget, err.needed), (5, 10));
+    }
+
+    #[test]
+    fn only_discord_has_a_message_limit() {
+        assert_eq!(DiscordRenderer.message_limit(), Some(DISCORD_MESSAGE_LIMIT));
+        assert_eq!(JsonRenderer::new().message_limit(), None);
+        assert_eq!(CsvRenderer::new().message_limit(), None);
+    }
+}
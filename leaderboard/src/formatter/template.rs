@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::UnknownPlaceholder;
+
+/// Text with `{name}` placeholders, e.g. "Last updated: {last_updated}".
+///
+/// # Details
+///
+/// Placeholder names are lowercase letters, digits and underscores. `{{` and `}}` stand for
+/// literal braces, and any other brace is left as is. Placeholders that aren't given a value
+/// are kept verbatim, so check a template against the names it'll be rendered with using
+/// [`Template::validate`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Template {
+    text: String,
+}
+
+/// A piece of a [`Template`].
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+impl Template {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into() }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn segments(&self) -> Vec<Segment<'_>> {
+        let mut segments = vec![];
+        let mut rest = self.text.as_str();
+        while let Some(start) = rest.find(['{', '}']) {
+            segments.push(Segment::Text(&rest[..start]));
+            let tail = &rest[start..];
+            if tail.starts_with("{{") || tail.starts_with("}}") {
+                segments.push(Segment::Text(&tail[..1]));
+                rest = &tail[2..];
+                continue;
+            }
+
+            let name = tail
+                .strip_prefix('{')
+                .and_then(|t| t.split_once('}'))
+                .map(|(name, _)| name)
+                .filter(|name| is_placeholder_name(name));
+            match name {
+                Some(name) => {
+                    segments.push(Segment::Placeholder(name));
+                    rest = &tail[name.len() + 2..];
+                }
+                None => {
+                    segments.push(Segment::Text(&tail[..1]));
+                    rest = &tail[1..];
+                }
+            }
+        }
+        segments.push(Segment::Text(rest));
+        segments
+    }
+
+    /// The names of every placeholder in the template, in order.
+    pub fn placeholders(&self) -> Vec<&str> {
+        self.segments()
+            .into_iter()
+            .filter_map(|s| match s {
+                Segment::Placeholder(name) => Some(name),
+                Segment::Text(_) => None,
+            })
+            .collect()
+    }
+
+    /// Fails on the first placeholder that isn't one of `known`.
+    pub fn validate(&self, known: &[&str]) -> Result<(), UnknownPlaceholder> {
+        match self.placeholders().into_iter().find(|p| !known.contains(p)) {
+            None => Ok(()),
+            Some(name) => Err(UnknownPlaceholder {
+                name: name.to_string(),
+                known: known.iter().map(|k| format!("{{{}}}", k)).collect(),
+            }),
+        }
+    }
+
+    pub fn render(&self, values: &HashMap<&str, String>) -> String {
+        self.segments()
+            .into_iter()
+            .map(|s| match s {
+                Segment::Text(text) => text.to_string(),
+                Segment::Placeholder(name) => match values.get(name) {
+                    Some(value) => value.clone(),
+                    None => format!("{{{}}}", name),
+                },
+            })
+            .collect()
+    }
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(text: &str) -> String {
+        Template::new(text).render(&HashMap::from([("name", "Bo".to_string())]))
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        assert_eq!(render("Hi {name}, {name}!"), "Hi Bo, Bo!");
+        assert_eq!(render("{missing} {name}"), "{missing} Bo");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render("{{name}} and }}{{"), "{name} and }{");
+        assert_eq!(render("{{{name}}}"), "{Bo}");
+    }
+
+    #[test]
+    fn malformed_braces_are_left_as_is() {
+        for text in [
+            "{ open",
+            "close }",
+            "{Name}",
+            "{}",
+            "{a b}",
+            "{unterminated",
+            "}{",
+        ] {
+            assert_eq!(render(text), text);
+        }
+    }
+
+    #[test]
+    fn placeholders_are_listed_in_order() {
+        let template = Template::new("{b} {{a}} {a} {b} {A}");
+
+        assert_eq!(template.placeholders(), ["b", "a", "b"]);
+    }
+
+    #[test]
+    fn validate_names_the_first_unknown_placeholder() {
+        let template = Template::new("{a} {{c}} {c} {d}");
+
+        assert!(template.validate(&["a", "c", "d"]).is_ok());
+        let err = template.validate(&["a", "b"]).unwrap_err();
+        assert_eq!(err.name, "c");
+        assert_eq!(err.known, ["{a}", "{b}"]);
+    }
+}
@@ -1,5 +1,9 @@
 pub mod api;
+pub mod clock;
 pub mod contracts;
 pub mod error;
 pub mod formatter;
 pub mod report_generator;
+
+#[cfg(test)]
+mod test_support;
@@ -1,11 +1,25 @@
-use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use log::error;
 
 use leaderboard::api::get_periodicals;
+use leaderboard::clock::{Clock, FixedClock, SystemClock};
 use leaderboard::contracts::active_contract::ActiveContractBuilder;
+use leaderboard::contracts::boosts::BoostCatalog;
+use leaderboard::contracts::contract_resolver::{ContractResolverChain, DatabaseResolver};
 use leaderboard::contracts::coop_flag::CoopFlag;
+use leaderboard::contracts::coop_source::{
+    CompositeCoopSource, CoopSource, DatabaseCoopSource, MajCoopSource, StaticCoopSource,
+};
+use leaderboard::contracts::forecast::ForecastConfig;
 use leaderboard::error;
-use leaderboard::report_generator::sruc::SrucTable;
-use time::OffsetDateTime;
+use leaderboard::formatter::table_renderer::renderer_by_name;
+use leaderboard::report_generator::coop_columns::TableLayout;
+use leaderboard::report_generator::coop_detail::CoopDetailReport;
+use leaderboard::report_generator::ranking::{default_snapshot_path, RankSnapshot};
+use leaderboard::report_generator::sruc::{SrucTable, SrucTemplate};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -17,34 +31,145 @@ async fn main() -> Result<()> {
         })
         .init();
 
-    let kev_open_contracts = get_periodicals()
-        .await?
+    // `LEADERBOARD_TIME` (unix seconds) renders the board as of that instant instead of now.
+    let clock: Arc<dyn Clock> = match std::env::var("LEADERBOARD_TIME") {
+        Ok(time) => Arc::new(FixedClock::from_unix_timestamp(time.parse()?)?),
+        Err(_) => Arc::new(SystemClock),
+    };
+
+    // `LEADERBOARD_FORMAT` picks the output: discord (default), markdown, csv, json or plain.
+    let format = std::env::var("LEADERBOARD_FORMAT").unwrap_or_else(|_| "discord".to_string());
+    let renderer = renderer_by_name(&format)
+        .ok_or_else(|| anyhow!("Unknown LEADERBOARD_FORMAT \"{}\"", format))?;
+
+    // `LEADERBOARD_LAYOUT` points to a TOML file with the columns to show.
+    let layout = match std::env::var("LEADERBOARD_LAYOUT") {
+        Ok(path) => TableLayout::load(path)?,
+        Err(_) => TableLayout::default(),
+    };
+
+    // `LEADERBOARD_TEMPLATE` points to a TOML file with the header and footer text.
+    let template = match std::env::var("LEADERBOARD_TEMPLATE") {
+        Ok(path) => SrucTemplate::load(path)?,
+        Err(_) => SrucTemplate::default(),
+    };
+
+    // `LEADERBOARD_FORECAST` forecasts every coop and adds the P10–P90 duration to the board.
+    let forecast_config =
+        std::env::var_os("LEADERBOARD_FORECAST").map(|_| ForecastConfig::default());
+
+    let periodicals = get_periodicals().await?;
+    let boost_catalog = Arc::new(
+        periodicals
+            .live_config
+            .as_ref()
+            .and_then(|c| c.boosts_config.as_ref())
+            .map(BoostCatalog::from_live_config)
+            .unwrap_or_default(),
+    );
+    let kev_open_contracts = periodicals
         .contracts
         .ok_or(error::EmptyContractsResponse)?
         .contracts;
 
     let most_recent_contract_codes: Vec<_> = kev_open_contracts
         .iter()
-        .filter(|&c| {
-            OffsetDateTime::now_utc().unix_timestamp() - c.start_time() as i64 <= 259_200i64
-        })
-        .map(|c| c.identifier())
+        .filter(|&c| clock.unix_timestamp() - c.start_time() as i64 <= 259_200i64)
+        .map(|c| c.identifier().to_string())
         .collect();
 
+    let mut coop_source = CompositeCoopSource::new().with_source(MajCoopSource::new());
+    let mut contract_resolvers = ContractResolverChain::standard();
+    if let Ok(path) = std::env::var("COOP_CODES_FILE") {
+        coop_source.add_source(StaticCoopSource::from_file(path)?);
+    }
+    if let Ok(url) = std::env::var("DATABASE_URL") {
+        coop_source.add_source(DatabaseCoopSource::connect(&url).await?);
+        contract_resolvers = contract_resolvers
+            .with_resolver_before("carpetsage", DatabaseResolver::connect(&url).await?);
+    }
+    for contract in &kev_open_contracts {
+        contract_resolvers.preload(contract.clone());
+    }
+
+    let rank_snapshot_path = default_snapshot_path();
+    // Without the previous ranks the boards just show every coop as new; they still render.
+    let mut rank_snapshot = match RankSnapshot::load(&rank_snapshot_path) {
+        Ok(rank_snapshot) => rank_snapshot,
+        Err(e) => {
+            error!("Starting from empty ranks: {:#}", e);
+            RankSnapshot::new()
+        }
+    };
+
+    // A failing source is skipped by `CompositeCoopSource`; if none of them answer, still render
+    // the (empty) boards instead of giving up.
+    let mut all_coop_codes = match coop_source
+        .coop_codes_batch(&most_recent_contract_codes, CoopFlag::Speedrun)
+        .await
+    {
+        Ok(all_coop_codes) => all_coop_codes,
+        Err(e) => {
+            error!("Cannot look up coop codes: {:#}", e);
+            HashMap::new()
+        }
+    };
+
     for contract_id in most_recent_contract_codes {
+        let coop_codes = all_coop_codes.remove(&contract_id).unwrap_or_default();
         let mut sr = ActiveContractBuilder::new()
-            .with_contract_id(contract_id)
+            .with_contract_id(&contract_id)
             .with_coop_flag(CoopFlag::Speedrun)
-            .build()
+            .build_with(&contract_resolvers)
             .await?;
 
-        let _ = sr.fill_coops().await;
+        sr.set_boost_catalog(boost_catalog.clone());
+        sr.set_clock(clock.clone());
+        sr.set_forecast_config(forecast_config);
+        let _ = sr.fill_coops_with_codes(coop_codes).await;
+
+        let mut sr_table = SrucTable::from_layout(&layout)
+            .with_clock(clock.clone())
+            .with_template(template.clone())
+            .with_contract(&sr)
+            .with_title(format!("# {} | Speedrun Leaderboard", sr.contract_name()));
+        // `LEADERBOARD_SUMMARY` adds the coop count, finished count and durations to the header.
+        if std::env::var_os("LEADERBOARD_SUMMARY").is_some() {
+            sr_table = sr_table.with_summary();
+        }
+        if forecast_config.is_some() {
+            sr_table = sr_table.with_forecast_range();
+        }
+        sr_table.extend_data_rows(sr.into_coops());
+        let ranking = sr_table.add_rank_columns(rank_snapshot.ranking(&contract_id));
+        // Only formats that are posted as messages get split; the others are written out whole.
+        let pages = match renderer.message_limit() {
+            Some(limit) => match sr_table.generate_pages(renderer.as_ref(), limit) {
+                Ok(pages) => pages,
+                Err(e) => {
+                    error!("Skipping leaderboard of \"{}\": {}", contract_id, e);
+                    continue;
+                }
+            },
+            None => vec![sr_table.generate(renderer.as_ref())],
+        };
+        // Only now that the board is shown is it what the next one's movement compares to.
+        rank_snapshot.record(contract_id, ranking);
+        for page in pages {
+            println!("{}", page);
+        }
 
-        let mut sr_table = SrucTable::new();
-        sr_table.add_data_rows(sr.coops().as_slice());
-        println!("# {} | Speedrun Leaderboard", sr.contract_name());
-        sr_table.generate();
+        // `LEADERBOARD_COOP` (a coop code) also prints when that coop reaches each of its goals.
+        if let Ok(coop_code) = std::env::var("LEADERBOARD_COOP") {
+            let coop = sr_table.data_rows().find(|c| c.coop_id() == coop_code);
+            if let Some(coop) = coop.filter(|_| renderer.supports_text()) {
+                let report = CoopDetailReport::new(coop).with_clock(clock.clone());
+                println!("{}", renderer.render_text(&report.generate()));
+            }
+        }
     }
 
+    rank_snapshot.save(&rank_snapshot_path)?;
+
     Ok(())
 }
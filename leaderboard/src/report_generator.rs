@@ -1 +1,4 @@
+pub mod coop_columns;
+pub mod coop_detail;
+pub mod ranking;
 pub mod sruc;
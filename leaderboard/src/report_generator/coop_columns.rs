@@ -0,0 +1,399 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use ei::ei::contract::PlayerGrade;
+
+use crate::{
+    contracts::coop::Coop,
+    formatter::{
+        discord_table::{Aggregate, ColumnWidth, DiscordTableColumn},
+        discord_timestamp::DiscordTimestampDisplay,
+        duration::Duration,
+        string_formatter::{self, Overflow, StringAlignment},
+    },
+};
+
+/// Every column a coop leaderboard can show, by the name used in layout configs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoopColumn {
+    /// The coop code, linked to its page on carpet.
+    Coop,
+    Boosted,
+    Tokens,
+    /// Predicted total duration of the coop.
+    Duration,
+    /// Predicted finishing time.
+    Finish,
+    Players,
+    /// Eggs the whole coop ships per hour.
+    ShippingRate,
+    /// Eggs laid offline that haven't been synced yet.
+    Offline,
+    Grade,
+    /// Percentage of the final goal shipped.
+    Progress,
+    GreenScroll,
+    /// How old the coop's data is.
+    Age,
+}
+
+impl CoopColumn {
+    pub const ALL: [CoopColumn; 12] = [
+        Self::Coop,
+        Self::Boosted,
+        Self::Tokens,
+        Self::Duration,
+        Self::Finish,
+        Self::Players,
+        Self::ShippingRate,
+        Self::Offline,
+        Self::Grade,
+        Self::Progress,
+        Self::GreenScroll,
+        Self::Age,
+    ];
+
+    /// The name of the column in layout configs, e.g. "green_scroll".
+    pub fn identifier(&self) -> &'static str {
+        match self {
+            Self::Coop => "coop",
+            Self::Boosted => "boosted",
+            Self::Tokens => "tokens",
+            Self::Duration => "duration",
+            Self::Finish => "finish",
+            Self::Players => "players",
+            Self::ShippingRate => "shipping_rate",
+            Self::Offline => "offline",
+            Self::Grade => "grade",
+            Self::Progress => "progress",
+            Self::GreenScroll => "green_scroll",
+            Self::Age => "age",
+        }
+    }
+
+    pub fn from_identifier(identifier: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.identifier() == identifier)
+    }
+
+    pub fn default_header(&self) -> &'static str {
+        match self {
+            Self::Coop => "Coop",
+            Self::Boosted => "Boosted",
+            Self::Tokens => "Tokens",
+            Self::Duration => "Duration",
+            Self::Finish => "Finish",
+            Self::Players => "Players",
+            Self::ShippingRate => "Ship/h",
+            Self::Offline => "Offline",
+            Self::Grade => "Grade",
+            Self::Progress => "Progress",
+            Self::GreenScroll => "Scroll",
+            Self::Age => "Age",
+        }
+    }
+
+    pub fn default_width(&self) -> usize {
+        match self {
+            Self::Coop => 8,
+            Self::Boosted => 9,
+            Self::Tokens => 8,
+            Self::Duration => 10,
+            Self::Finish => 20,
+            Self::Players => 9,
+            Self::ShippingRate => 9,
+            Self::Offline => 9,
+            Self::Grade => 7,
+            Self::Progress => 10,
+            Self::GreenScroll => 8,
+            Self::Age => 7,
+        }
+    }
+
+    fn value_fn(&self) -> fn(&Coop) -> String {
+        match self {
+            Self::Coop => |c| {
+                format!(
+                    "[\u{29c9}](<https://eicoop-carpet.netlify.app/{}/{}>) `{}",
+                    c.contract_id(),
+                    c.coop_id(),
+                    c.stripped_coop_id()
+                )
+            },
+            Self::Boosted => |c| c.boosted_count().to_string(),
+            Self::Tokens => |c| c.total_tokens().to_string(),
+            Self::Duration => |c| {
+                c.total_predicted_duration()
+                    .format_within(c.contract_length())
+            },
+            Self::Finish => |c| {
+                format!(
+                    "`{}",
+                    c.finishing_time()
+                        .display(DiscordTimestampDisplay::FullDateTime)
+                )
+            },
+            Self::Players => |c| c.player_count().to_string(),
+            Self::ShippingRate => |c| string_formatter::egg_amount(c.shipping_rate_per_hour()),
+            Self::Offline => |c| string_formatter::egg_amount(c.offline_eggs()),
+            Self::Grade => |c| grade_name(c.grade()).to_string(),
+            Self::Progress => |c| format!("{:.1}%", c.progress() * 100.0),
+            Self::GreenScroll => |c| {
+                if c.green_scrolled() {
+                    "\u{2713}".to_string()
+                } else {
+                    "".to_string()
+                }
+            },
+            Self::Age => |c| c.data_age().format(),
+        }
+    }
+
+    /// The number behind the column's cells: durations in seconds, times in unix seconds and
+    /// progress in percent.
+    fn number_fn(&self) -> Option<fn(&Coop) -> f64> {
+        match self {
+            Self::Boosted => Some(|c| c.boosted_count() as f64),
+            Self::Tokens => Some(|c| c.total_tokens() as f64),
+            Self::Duration => Some(|c| c.total_predicted_duration().duration_in_seconds as f64),
+            Self::Finish => Some(|c| c.finishing_time().unix_timestamp() as f64),
+            Self::Players => Some(|c| c.player_count() as f64),
+            Self::ShippingRate => Some(Coop::shipping_rate_per_hour),
+            Self::Offline => Some(Coop::offline_eggs),
+            Self::Progress => Some(|c| c.progress() * 100.0),
+            Self::Age => Some(|c| c.data_age().duration_in_seconds as f64),
+            Self::Coop | Self::Grade | Self::GreenScroll => None,
+        }
+    }
+
+    /// The column with its default header, width and alignment. Counts can be totalled,
+    /// durations and rates summarised, see [`Aggregate`].
+    pub fn column(&self) -> DiscordTableColumn<Coop> {
+        let mut col = DiscordTableColumn::new(
+            self.default_header(),
+            self.value_fn(),
+            self.default_width(),
+            StringAlignment::Centered,
+        );
+        if let Some(number_fn) = self.number_fn() {
+            col = col.with_number(number_fn);
+        }
+        let count = |v: f64| format!("{:.0}", v);
+        let summary = [
+            Aggregate::Min,
+            Aggregate::Median,
+            Aggregate::Mean,
+            Aggregate::Max,
+        ];
+
+        match self {
+            Self::Boosted => col.with_reducer(
+                |c: &Coop| c.boosted_count() as f64,
+                count,
+                &[Aggregate::Total],
+            ),
+            Self::Tokens => col.with_reducer(
+                |c: &Coop| c.total_tokens() as f64,
+                count,
+                &[Aggregate::Total],
+            ),
+            Self::Players => col.with_reducer(
+                |c: &Coop| c.player_count() as f64,
+                count,
+                &[Aggregate::Total],
+            ),
+            Self::Duration => col.with_reducer(
+                |c: &Coop| c.total_predicted_duration().duration_in_seconds as f64,
+                |v| Duration::new(v.round() as i64).format_too_long(),
+                &summary,
+            ),
+            Self::ShippingRate => col.with_reducer(
+                Coop::shipping_rate_per_hour,
+                string_formatter::egg_amount,
+                &[Aggregate::Total, Aggregate::Mean],
+            ),
+            Self::Progress => col.with_reducer(
+                |c: &Coop| c.progress() * 100.0,
+                |v| format!("{:.1}%", v),
+                &summary,
+            ),
+            _ => col,
+        }
+    }
+}
+
+fn grade_name(grade: PlayerGrade) -> &'static str {
+    match grade {
+        PlayerGrade::GradeUnset => "-",
+        PlayerGrade::GradeC => "C",
+        PlayerGrade::GradeB => "B",
+        PlayerGrade::GradeA => "A",
+        PlayerGrade::GradeAa => "AA",
+        PlayerGrade::GradeAaa => "AAA",
+    }
+}
+
+/// One column of a [`TableLayout`]. Anything left out uses the column's defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnSpec {
+    pub column: CoopColumn,
+    pub header: Option<String>,
+    pub width: Option<ColumnWidth>,
+    pub alignment: Option<StringAlignment>,
+    pub overflow: Option<Overflow>,
+}
+
+impl ColumnSpec {
+    pub fn new(column: CoopColumn) -> Self {
+        Self {
+            column,
+            header: None,
+            width: None,
+            alignment: None,
+            overflow: None,
+        }
+    }
+
+    pub fn build(&self) -> DiscordTableColumn<Coop> {
+        let mut col = self.column.column();
+        if let Some(header) = &self.header {
+            col = col.with_name(header);
+        }
+        if let Some(width) = self.width {
+            col = col.with_width(width);
+        }
+        if let Some(alignment) = self.alignment {
+            col = col.with_alignment(alignment);
+        }
+        if let Some(overflow) = self.overflow {
+            col = col.with_overflow(overflow);
+        }
+        col
+    }
+}
+
+/// Which columns a coop leaderboard shows, in order.
+///
+/// # Details
+///
+/// Layouts are written in TOML, one `[[columns]]` table per column:
+///
+/// ```toml
+/// [[columns]]
+/// column = "coop"
+///
+/// [[columns]]
+/// column = "duration"
+/// header = "Time"
+/// width = { min = 6 }
+/// alignment = "right"
+/// ```
+///
+/// Column names are the [`CoopColumn::identifier`]s. The default layout is coop, boosted,
+/// tokens, duration and finish. Aggregate rows go under the data, in order, e.g.
+/// `aggregates = ["median", "total"]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableLayout {
+    pub columns: Vec<ColumnSpec>,
+    #[serde(default)]
+    pub aggregates: Vec<Aggregate>,
+}
+
+impl TableLayout {
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml).context("Cannot parse table layout")
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let toml = std::fs::read_to_string(path)
+            .context(format!("Cannot read table layout \"{}\"", path.display()))?;
+        Self::from_toml(&toml)
+    }
+
+    pub fn build_columns(&self) -> Vec<DiscordTableColumn<Coop>> {
+        self.columns.iter().map(ColumnSpec::build).collect()
+    }
+}
+
+impl Default for TableLayout {
+    fn default() -> Self {
+        Self {
+            columns: [
+                CoopColumn::Coop,
+                CoopColumn::Boosted,
+                CoopColumn::Tokens,
+                CoopColumn::Duration,
+                CoopColumn::Finish,
+            ]
+            .into_iter()
+            .map(ColumnSpec::new)
+            .collect(),
+            aggregates: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_round_trip() {
+        for column in CoopColumn::ALL {
+            assert_eq!(
+                CoopColumn::from_identifier(column.identifier()),
+                Some(column)
+            );
+        }
+        assert_eq!(CoopColumn::from_identifier("elr"), None);
+    }
+
+    #[test]
+    fn layout_reads_snake_case_names() {
+        let layout = TableLayout::from_toml(
+            r#"
+            aggregates = ["median", "total"]
+
+            [[columns]]
+            column = "shipping_rate"
+            header = "Rate"
+            width = { min = 6 }
+            alignment = "right"
+            overflow = "truncate"
+
+            [[columns]]
+            column = "green_scroll"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            layout.columns,
+            [
+                ColumnSpec {
+                    header: Some("Rate".to_string()),
+                    width: Some(ColumnWidth::Auto { min: 6, max: None }),
+                    alignment: Some(StringAlignment::Right),
+                    overflow: Some(Overflow::Truncate),
+                    ..ColumnSpec::new(CoopColumn::ShippingRate)
+                },
+                ColumnSpec::new(CoopColumn::GreenScroll),
+            ]
+        );
+        assert_eq!(layout.aggregates, [Aggregate::Median, Aggregate::Total]);
+    }
+
+    #[test]
+    fn layout_rejects_unknown_names() {
+        assert!(TableLayout::from_toml("[[columns]]\ncolumn = \"elr\"").is_err());
+        assert!(
+            TableLayout::from_toml("[[columns]]\ncolumn = \"coop\"\nalignment = \"Right\"")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    contracts::coop::Coop,
+    formatter::discord_timestamp::{DiscordTimestamp, DiscordTimestampDisplay},
+};
+
+/// A single coop's goals and when it's predicted to reach each of them.
+pub struct CoopDetailReport<'a> {
+    coop: &'a Coop,
+    clock: Arc<dyn Clock>,
+}
+
+impl<'a> CoopDetailReport<'a> {
+    pub fn new(coop: &'a Coop) -> Self {
+        Self {
+            coop,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// The clock "Last updated" is read from, the system clock by default.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn get_goal_lines(&self) -> String {
+        self.coop
+            .goal_timeline()
+            .iter()
+            .map(|goal| match goal.finishing_time {
+                None => format!("Goal {} reached ({})\n", goal.number, goal.reward()),
+                Some(time) => format!(
+                    "Goal {} at {} ({})\n",
+                    goal.number,
+                    time.display(DiscordTimestampDisplay::FullDateTime),
+                    goal.reward()
+                ),
+            })
+            .collect()
+    }
+
+    pub fn generate(&self) -> String {
+        format!(
+            "# {} | {}\n\
+            Last updated: {}\n\
+            \n\
+            {}\
+            \n",
+            self.coop.coop_id(),
+            self.coop.contract_id(),
+            DiscordTimestamp::new_from_now_with(self.clock.as_ref())
+                .display(DiscordTimestampDisplay::Relative),
+            self.get_goal_lines(),
+        )
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(self.generate().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ei::ei::RewardType;
+
+    use crate::clock::FixedClock;
+    use crate::test_support::{contract, shipping_coop_status};
+
+    use super::*;
+
+    #[test]
+    fn lists_every_goal_with_its_reward() {
+        let mut contract = contract(&[500.0, 11_000.0]);
+        for (goal, (reward_type, amount)) in contract.grade_specs[0]
+            .goals
+            .iter_mut()
+            .zip([(RewardType::Gold, 500.0), (RewardType::BoostToken, 3.0)])
+        {
+            goal.reward_type = Some(reward_type as i32);
+            goal.reward_amount = Some(amount);
+        }
+        let coop = Coop::new(shipping_coop_status(1000.0, 10.0), contract, 1_700_000_000).unwrap();
+        let clock = FixedClock::from_unix_timestamp(1_700_000_060).unwrap();
+
+        assert_eq!(
+            CoopDetailReport::new(&coop)
+                .with_clock(Arc::new(clock))
+                .generate(),
+            "# test-coop | test-contract\n\
+            Last updated: <t:1700000060:R>\n\
+            \n\
+            Goal 1 reached (500 gold)\n\
+            Goal 2 at <t:1700001000:f> (3 boost token)\n\
+            \n"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::api::contract_archive::write_atomically;
+use crate::contracts::coop_metrics::CoopMetrics;
+
+/// Leaderboard positions of a contract's coops, by coop ID.
+///
+/// # Details
+///
+/// Uses competition ranking: coops that compare equal under [`CoopMetrics::leaderboard_cmp`]
+/// share a rank and the next rank is skipped, e.g. 1, 2, 2, 4.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Ranking {
+    ranks: HashMap<String, u32>,
+}
+
+impl Ranking {
+    /// Ranks `metrics`, which have to be in leaderboard order already.
+    pub fn from_sorted(metrics: &[&CoopMetrics]) -> Self {
+        let mut ranks = HashMap::new();
+        let mut rank = 0;
+        for (i, m) in metrics.iter().enumerate() {
+            let tied = i > 0 && metrics[i - 1].leaderboard_cmp(m) == Ordering::Equal;
+            if !tied {
+                rank = i as u32 + 1;
+            }
+            ranks.insert(m.coop_id.clone(), rank);
+        }
+        Self { ranks }
+    }
+
+    pub fn rank(&self, coop_id: &str) -> Option<u32> {
+        self.ranks.get(coop_id).copied()
+    }
+}
+
+/// 🥇, 🥈 or 🥉 for the top three ranks, otherwise the rank itself.
+pub fn medal(rank: u32) -> String {
+    match rank {
+        1 => "\u{1f947}".to_string(),
+        2 => "\u{1f948}".to_string(),
+        3 => "\u{1f949}".to_string(),
+        _ => rank.to_string(),
+    }
+}
+
+/// How a coop moved since the previous ranking: "▲2", "▼1", "–" if it stayed or "new".
+pub fn movement(previous: Option<u32>, current: u32) -> String {
+    match previous {
+        None => "new".to_string(),
+        Some(previous) => match previous.cmp(&current) {
+            Ordering::Greater => format!("\u{25b2}{}", previous - current),
+            Ordering::Less => format!("\u{25bc}{}", current - previous),
+            Ordering::Equal => "\u{2013}".to_string(),
+        },
+    }
+}
+
+/// The rankings of the previous render, per contract, kept on disk between runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RankSnapshot {
+    contracts: HashMap<String, Ranking>,
+}
+
+impl RankSnapshot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty snapshot if nothing was saved at `path` yet.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        let json = std::fs::read_to_string(path)
+            .context(format!("Cannot read rank snapshot \"{}\"", path.display()))?;
+        serde_json::from_str(&json)
+            .context(format!("Cannot parse rank snapshot \"{}\"", path.display()))
+    }
+
+    /// Swaps the file in whole, so an interrupted save leaves the previous snapshot intact.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        write_atomically(path, serde_json::to_string(self)?.as_bytes())
+            .context(format!("Cannot write rank snapshot \"{}\"", path.display()))
+    }
+
+    pub fn ranking(&self, contract_id: &str) -> Option<&Ranking> {
+        self.contracts.get(contract_id)
+    }
+
+    pub fn record(&mut self, contract_id: impl Into<String>, ranking: Ranking) {
+        self.contracts.insert(contract_id.into(), ranking);
+    }
+}
+
+/// `RANK_SNAPSHOT_FILE` if set, otherwise `leaderboard_ranks.json` in the temp directory.
+pub fn default_snapshot_path() -> PathBuf {
+    std::env::var_os("RANK_SNAPSHOT_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("leaderboard_ranks.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formatter::duration::Duration;
+
+    use super::*;
+
+    fn metrics(coop_id: &str, duration: i64, boosted_count: u32) -> CoopMetrics {
+        CoopMetrics {
+            coop_id: coop_id.to_string(),
+            total_predicted_duration: Duration::new(duration),
+            boosted_count,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ties_share_a_rank_and_skip_the_next() {
+        let metrics = [
+            metrics("a", 100, 5),
+            metrics("b", 200, 5),
+            metrics("c", 200, 5),
+            metrics("d", 200, 4),
+        ];
+        let ranking = Ranking::from_sorted(&metrics.iter().collect::<Vec<_>>());
+
+        let ranks: Vec<_> = ["a", "b", "c", "d", "e"]
+            .into_iter()
+            .map(|id| ranking.rank(id))
+            .collect();
+        assert_eq!(ranks, [Some(1), Some(2), Some(2), Some(4), None]);
+    }
+
+    #[test]
+    fn medals_for_the_top_three() {
+        assert_eq!(medal(1), "\u{1f947}");
+        assert_eq!(medal(3), "\u{1f949}");
+        assert_eq!(medal(4), "4");
+    }
+
+    #[test]
+    fn movement_compares_to_the_previous_rank() {
+        assert_eq!(movement(Some(4), 1), "\u{25b2}3");
+        assert_eq!(movement(Some(1), 2), "\u{25bc}1");
+        assert_eq!(movement(Some(2), 2), "\u{2013}");
+        assert_eq!(movement(None, 2), "new");
+    }
+
+    #[test]
+    fn snapshot_round_trips_and_rejects_garbage() {
+        let path = std::env::temp_dir().join(format!("ranks-test-{}.json", std::process::id()));
+        let mut snapshot = RankSnapshot::new();
+        snapshot.record("contract", Ranking::from_sorted(&[&metrics("a", 100, 0)]));
+
+        snapshot.save(&path).unwrap();
+        assert_eq!(RankSnapshot::load(&path).unwrap(), snapshot);
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(RankSnapshot::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(RankSnapshot::load(&path).unwrap(), RankSnapshot::new());
+    }
+}
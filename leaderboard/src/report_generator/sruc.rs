@@ -1,90 +1,348 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
 use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::Arc;
 
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::coop_columns::{CoopColumn, TableLayout};
+use super::ranking::{medal, movement, Ranking};
 use crate::{
-    contracts::coop::Coop,
+    clock::{Clock, SystemClock},
+    contracts::{active_contract::ActiveContract, contract_summary::ContractSummary, coop::Coop},
+    error::{PageOverflow, UnknownPlaceholder},
     formatter::{
         discord_table::{DiscordTable, DiscordTableColumn},
         discord_timestamp::{DiscordTimestamp, DiscordTimestampDisplay},
+        duration::Duration,
         string_formatter::StringAlignment,
+        table_renderer::{self, TableRenderer},
+        template::Template,
     },
 };
 
-#[derive(Default)]
-pub struct SrucTable(DiscordTable<Coop>);
+/// Coop data older than this (30 minutes) gets a warning in the header.
+const STALE_DATA_SECS: i64 = 30 * 60;
+
+/// The text above and below a [`SrucTable`], so communities can brand their board.
+///
+/// # Details
+///
+/// Both are [`Template`]s over [`SrucTemplate::VARIABLES`]. `{title}`, `{modifiers}`,
+/// `{staleness_warning}` and `{summary}` end in a newline when they're not empty, so they can sit
+/// on a line of their own. `{summary}` stays empty unless the table is built
+/// [`SrucTable::with_summary`]. In TOML:
+///
+/// ```toml
+/// header = """
+/// # {contract_name} ({egg})
+/// Last updated: {last_updated} | {coop_count} coops
+/// {modifiers}{staleness_warning}
+/// """
+/// footer = "*Brought to you by the Speedrun Club*\n"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SrucTemplate {
+    pub header: Template,
+    pub footer: Template,
+}
+
+impl SrucTemplate {
+    pub const VARIABLES: [&'static str; 10] = [
+        "title",
+        "contract_name",
+        "contract_id",
+        "egg",
+        "last_updated",
+        "coop_count",
+        "failed_count",
+        "modifiers",
+        "staleness_warning",
+        "summary",
+    ];
+
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let template: Self = toml::from_str(toml).context("Cannot parse leaderboard template")?;
+        template.validate()?;
+        Ok(template)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let toml = std::fs::read_to_string(path).context(format!(
+            "Cannot read leaderboard template \"{}\"",
+            path.display()
+        ))?;
+        Self::from_toml(&toml)
+    }
+
+    pub fn validate(&self) -> Result<(), UnknownPlaceholder> {
+        self.header.validate(&Self::VARIABLES)?;
+        self.footer.validate(&Self::VARIABLES)
+    }
+}
+
+impl Default for SrucTemplate {
+    fn default() -> Self {
+        Self {
+            header: Template::new(
+                "{title}\
+                Last updated: {last_updated}\n\
+                {modifiers}\
+                {staleness_warning}\
+                {summary}\
+                \n",
+            ),
+            footer: Template::new(
+                "`Primary order based off of duration`\n\
+                \n\
+                *`!!sruc` to summon an update!*\n\
+                *Note that this is NOT a Wonky command, and is still generated by WHAL-Int-rs*\n\
+                \n\
+                \n",
+            ),
+        }
+    }
+}
+
+pub struct SrucTable {
+    table: DiscordTable<Coop>,
+    clock: Arc<dyn Clock>,
+    title: Option<String>,
+    template: SrucTemplate,
+    contract_values: HashMap<&'static str, String>,
+    summary: bool,
+}
 
 impl SrucTable {
     pub fn new() -> Self {
+        Self::from_layout(&TableLayout::default())
+    }
+
+    /// A leaderboard with the columns of `layout`, e.g. one loaded from a server's config.
+    pub fn from_layout(layout: &TableLayout) -> Self {
         let mut table = DiscordTable::new();
+        for col in layout.build_columns() {
+            table.add_column(col);
+        }
+        for &aggregate in &layout.aggregates {
+            table.add_aggregate_row(aggregate);
+        }
 
-        let name_col = DiscordTableColumn::new(
-            "Coop",
-            |c: Coop| {
-                format!(
-                    "[\u{29c9}](<https://eicoop-carpet.netlify.app/{}/{}>) `{}",
-                    c.contract_id(),
-                    c.coop_id(),
-                    c.stripped_coop_id()
-                )
-            },
-            8,
-            StringAlignment::Centered,
-        );
-        let boosted_col = DiscordTableColumn::new(
-            "Boosted",
-            |c: Coop| c.boosted_count().to_string(),
-            9,
-            StringAlignment::Centered,
-        );
-        let token_col = DiscordTableColumn::new(
-            "Tokens",
-            |c: Coop| c.total_tokens().to_string(),
-            8,
-            StringAlignment::Centered,
-        );
-        let dur_col = DiscordTableColumn::new(
-            "Duration",
-            |c: Coop| c.total_predicted_duration().format_too_long(),
-            10,
+        Self {
+            table,
+            clock: Arc::new(SystemClock),
+            title: None,
+            template: SrucTemplate::default(),
+            contract_values: HashMap::new(),
+            summary: false,
+        }
+    }
+
+    /// A line above everything else, e.g. "# Contract | Speedrun Leaderboard".
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_template(mut self, template: SrucTemplate) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Fills the contract's name, ID, egg and failed coop count into the template.
+    pub fn with_contract(mut self, contract: &ActiveContract) -> Self {
+        self.contract_values = HashMap::from([
+            ("contract_name", contract.contract_name().to_string()),
+            ("contract_id", contract.contract_id().to_string()),
+            ("egg", contract.egg_name()),
+            (
+                "failed_count",
+                contract.failed_coop_codes().len().to_string(),
+            ),
+        ]);
+        self
+    }
+
+    /// The clock "Last updated" is read from, the system clock by default.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Adds a "Range" column with the P10–P90 forecast duration of each coop. Coops need a
+    /// forecast for it, see [`ActiveContract::set_forecast_config`].
+    pub fn with_forecast_range(mut self) -> Self {
+        let range_col = DiscordTableColumn::new(
+            "Range",
+            |c: &Coop| c.forecast().map(|f| f.duration_range()).unwrap_or_default(),
+            17,
             StringAlignment::Centered,
         );
-        let finish_col = DiscordTableColumn::new(
-            "Finish",
-            |c: Coop| {
-                format!(
-                    "`{}",
-                    c.finishing_time()
-                        .display(DiscordTimestampDisplay::FullDateTime)
-                )
-            },
-            20,
+        self.add_column(range_col);
+        self
+    }
+
+    /// Puts a "Rank" column (medals for the top three) in front, plus a "Move" column if there's a
+    /// `previous` ranking to compare to. Call this after adding the rows; the returned ranking is
+    /// the one to keep for next time.
+    pub fn add_rank_columns(&mut self, previous: Option<&Ranking>) -> Ranking {
+        let metrics: Vec<_> = self.data_rows().map(|c| c.metrics()).collect();
+        let ranking = Ranking::from_sorted(&metrics);
+
+        let mut index = 0;
+        let current = ranking.clone();
+        let rank_col = DiscordTableColumn::new(
+            "Rank",
+            move |c: &Coop| current.rank(c.coop_id()).map(medal).unwrap_or_default(),
+            4,
             StringAlignment::Centered,
         );
+        self.insert_column(index, rank_col);
+        index += 1;
 
-        table.add_column(name_col);
-        table.add_column(boosted_col);
-        table.add_column(token_col);
-        table.add_column(dur_col);
-        table.add_column(finish_col);
-
-        Self(table)
-    }
-
-    pub fn generate(self) {
-        println!(
-            "Last updated: {}\n\
-            \n\
-            {}\n\
-            {}\
-            `Primary order based off of duration`\n\
-            \n\
-            *`!!sruc` to summon an update!*\n\
-            *Note that this is NOT a Wonky command, and is still generated by WHAL-Int-rs*\n\
-            \n\
-            \n",
-            DiscordTimestamp::new_from_now().display(DiscordTimestampDisplay::Relative),
-            self.get_table_header(),
-            self.get_table_body(),
-        );
+        if let Some(previous) = previous {
+            let (previous, current) = (previous.clone(), ranking.clone());
+            let move_col = DiscordTableColumn::new(
+                "Move",
+                move |c: &Coop| match current.rank(c.coop_id()) {
+                    Some(rank) => movement(previous.rank(c.coop_id()), rank),
+                    None => "".to_string(),
+                },
+                4,
+                StringAlignment::Centered,
+            );
+            self.insert_column(index, move_col);
+        }
+
+        ranking
+    }
+
+    /// Puts an overview of the coops (count, finished, fastest and median duration) in the header.
+    pub fn with_summary(mut self) -> Self {
+        self.summary = true;
+        self
+    }
+
+    fn get_summary(&self) -> String {
+        if !self.summary {
+            return "".to_string();
+        }
+
+        let metrics: Vec<_> = self.data_rows().map(|c| c.metrics()).collect();
+        format!("{}\n", ContractSummary::from_metrics(&metrics))
+    }
+
+    /// Adds an "Age" column with how old each coop's status response is.
+    pub fn with_data_age(mut self) -> Self {
+        self.add_column(CoopColumn::Age.column());
+        self
+    }
+
+    fn get_staleness_warning(&self) -> String {
+        let stale_limit = Duration::new(STALE_DATA_SECS);
+        match self.data_rows().map(|c| c.data_age()).max() {
+            Some(age) if age > stale_limit => {
+                format!("`Warning: some coop data is {} old`\n", age.format())
+            }
+            _ => "".to_string(),
+        }
+    }
+
+    /// The modifiers of the listed coops' grades, one line per distinct set, for the header.
+    fn get_modifiers_header(&self) -> String {
+        let mut modifier_sets: Vec<String> = vec![];
+        for coop in self.data_rows().filter(|c| !c.modifiers().is_empty()) {
+            let modifiers = coop.modifiers().to_string();
+            if !modifier_sets.contains(&modifiers) {
+                modifier_sets.push(modifiers);
+            }
+        }
+
+        modifier_sets
+            .iter()
+            .map(|m| format!("Modifiers: {}\n", m))
+            .collect()
+    }
+
+    fn template_values(&self) -> HashMap<&str, String> {
+        let mut values: HashMap<&str, String> = SrucTemplate::VARIABLES
+            .iter()
+            .map(|&name| (name, "".to_string()))
+            .collect();
+        values.insert("failed_count", "0".to_string());
+        values.extend(self.contract_values.clone());
+        values.extend([
+            (
+                "title",
+                self.title
+                    .as_ref()
+                    .map(|t| format!("{}\n", t))
+                    .unwrap_or_default(),
+            ),
+            (
+                "last_updated",
+                DiscordTimestamp::new_from_now_with(self.clock.as_ref())
+                    .display(DiscordTimestampDisplay::Relative),
+            ),
+            ("coop_count", self.data_rows().count().to_string()),
+            ("modifiers", self.get_modifiers_header()),
+            ("staleness_warning", self.get_staleness_warning()),
+            ("summary", self.get_summary()),
+        ]);
+        values
+    }
+
+    /// The text above and below the table in `renderer`'s format, empty for data-only formats.
+    fn get_header_and_footer(&self, renderer: &dyn TableRenderer) -> (String, String) {
+        if !renderer.supports_text() {
+            return ("".to_string(), "".to_string());
+        }
+
+        let values = self.template_values();
+        let header = self.template.header.render(&values);
+        let footer = self.template.footer.render(&values);
+
+        (renderer.render_text(&header), renderer.render_text(&footer))
+    }
+
+    /// The whole leaderboard in `renderer`'s format. Data-only formats get just the table.
+    pub fn generate(&self, renderer: &dyn TableRenderer) -> String {
+        let (header, footer) = self.get_header_and_footer(renderer);
+        format!(
+            "{}{}{}",
+            header,
+            renderer.render_table(&self.cells()),
+            footer
+        )
+    }
+
+    /// The leaderboard split into messages of at most `budget` characters, e.g. the renderer's
+    /// [`TableRenderer::message_limit`], see [`table_renderer::paginate`].
+    pub fn generate_pages(
+        &self,
+        renderer: &dyn TableRenderer,
+        budget: usize,
+    ) -> Result<Vec<String>, PageOverflow> {
+        let (header, footer) = self.get_header_and_footer(renderer);
+        table_renderer::paginate(renderer, &self.cells(), &header, &footer, budget)
+    }
+
+    pub fn write_to(
+        &self,
+        renderer: &dyn TableRenderer,
+        writer: &mut impl Write,
+    ) -> io::Result<()> {
+        writer.write_all(self.generate(renderer).as_bytes())
+    }
+}
+
+impl Default for SrucTable {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -92,12 +350,55 @@ impl Deref for SrucTable {
     type Target = DiscordTable<Coop>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.table
     }
 }
 
 impl DerefMut for SrucTable {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::contracts::forecast::ForecastConfig;
+    use crate::formatter::table_renderer::JsonRenderer;
+    use crate::test_support::{contract, shipping_coop_status};
+
+    use super::*;
+
+    #[test]
+    fn forecast_range_column_shows_p10_to_p90() {
+        // 3h10m of eggs at one a second.
+        let mut coop = Coop::new(
+            shipping_coop_status(0.0, 1.0),
+            contract(&[11_400.0]),
+            1_700_000_000,
+        )
+        .unwrap();
+        coop.refresh_forecast(&ForecastConfig::new(1, 0.0, 0.0, 0.0, 0).unwrap());
+        let mut table = SrucTable::new().with_forecast_range();
+        table.extend_data_rows([coop]);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&table.generate(&JsonRenderer::new())).unwrap();
+        assert_eq!(json[0]["Range"], "3h10m\u{2013}3h10m");
+    }
+
+    #[test]
+    fn default_template_is_valid() {
+        assert!(SrucTemplate::default().validate().is_ok());
+    }
+
+    #[test]
+    fn toml_templates_are_validated() {
+        let template = SrucTemplate::from_toml("header = \"# {contract_name}\\n\"").unwrap();
+        assert_eq!(template.header.text(), "# {contract_name}\n");
+        assert_eq!(template.footer, SrucTemplate::default().footer);
+
+        let err = SrucTemplate::from_toml("footer = \"{nope}\"").unwrap_err();
+        assert!(err.downcast_ref::<UnknownPlaceholder>().is_some());
+        assert!(SrucTemplate::from_toml("heading = \"\"").is_err());
     }
 }
@@ -0,0 +1,51 @@
+//! Fixtures shared by the unit tests.
+
+use ei::ei::contract::{Goal, GradeSpec, PlayerGrade};
+use ei::ei::contract_coop_status_response::ContributionInfo;
+use ei::ei::{Contract, ContractCoopStatusResponse};
+
+/// How long coops on [`contract`] have (3 days).
+pub(crate) const CONTRACT_LENGTH: f64 = 3.0 * 86_400.0;
+
+/// A contract with a single AAA grade whose goals are `goals`.
+pub(crate) fn contract(goals: &[f64]) -> Contract {
+    Contract {
+        identifier: Some("test-contract".to_string()),
+        grade_specs: vec![GradeSpec {
+            grade: Some(PlayerGrade::GradeAaa as i32),
+            length_seconds: Some(CONTRACT_LENGTH),
+            goals: goals
+                .iter()
+                .map(|&amount| Goal {
+                    target_amount: Some(amount),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+/// The status of an empty AAA coop on [`contract`]; fill in the rest with struct update syntax.
+pub(crate) fn coop_status() -> ContractCoopStatusResponse {
+    ContractCoopStatusResponse {
+        contract_identifier: Some("test-contract".to_string()),
+        coop_identifier: Some("test-coop".to_string()),
+        grade: Some(PlayerGrade::GradeAaa as i32),
+        ..Default::default()
+    }
+}
+
+/// A coop that just started, has shipped `shipped` eggs and ships `rate` eggs a second.
+pub(crate) fn shipping_coop_status(shipped: f64, rate: f64) -> ContractCoopStatusResponse {
+    ContractCoopStatusResponse {
+        total_amount: Some(shipped),
+        seconds_remaining: Some(CONTRACT_LENGTH),
+        contributors: vec![ContributionInfo {
+            contribution_rate: Some(rate),
+            ..Default::default()
+        }],
+        ..coop_status()
+    }
+}